@@ -0,0 +1,557 @@
+use std::{
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures_core::Stream;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        lookup_host,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpSocket, TcpStream,
+    },
+    sync::mpsc,
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{
+    client::{expect_json, expect_ok, unexpected, Notification},
+    command::Command,
+    connection::{greeted, socket},
+    error::{BiteError, Result},
+    frame::{Frame, FrameDecoder, READ_SIZE},
+    options::ConnectOptions,
+    requests::{Requests, Waiters},
+    response::Response,
+};
+
+// Everything the tasks and the callers share. The lock is never held across
+// an await.
+type Shared = Waiters<mpsc::UnboundedSender<Response>, mpsc::UnboundedSender<Notification>>;
+
+// The same commands as Client, for tokio. A reader task routes every reply
+// to the call waiting for it, so calls can run at the same time from many
+// tasks, and a writer task sends the frames in order, so a call that gets
+// dropped halfway never leaves half a frame on the wire.
+//
+// There's no reconnection here, when the connection drops every call fails
+// with BiteError::Disconnected and the subscriptions end. It only speaks
+// plain TCP, options with TLS or a socket file are refused.
+pub struct AsyncClient {
+    id: u16,
+    options: ConnectOptions,
+    shared: Arc<Mutex<Shared>>,
+    frames: mpsc::UnboundedSender<Frame>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+// The updates of a subscription as a Stream, it ends with the connection.
+// Dropping it stops routing them.
+pub struct Subscription {
+    id: u16,
+    command: Command,
+    events: mpsc::UnboundedReceiver<Notification>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl AsyncClient {
+    pub async fn connect(addr: impl ToString) -> Result<AsyncClient> {
+        AsyncClient::connect_with(ConnectOptions::new().addr(addr)).await
+    }
+
+    pub async fn connect_with(options: ConnectOptions) -> Result<AsyncClient> {
+        options.plain_tcp()?;
+
+        let mut last_err = io::Error::new(ErrorKind::InvalidInput, "no address to connect to");
+
+        for addr in options.addrs.clone() {
+            let resolved = lookup_host(addr).await.map_err(BiteError::Connect)?;
+
+            for addr in resolved {
+                match timeout(options.connect_timeout, connect(addr, &options)).await {
+                    Ok(Ok(stream)) => return AsyncClient::start(stream, options).await,
+                    Ok(Err(err)) => last_err = err,
+                    Err(_) => last_err = ErrorKind::TimedOut.into(),
+                }
+            }
+        }
+
+        Err(BiteError::Connect(last_err))
+    }
+
+    async fn start(stream: TcpStream, options: ConnectOptions) -> Result<AsyncClient> {
+        let (mut read, write) = stream.into_split();
+        let mut decoder = FrameDecoder::new();
+
+        let id = match timeout(options.connect_timeout, handshake(&mut read, &mut decoder)).await {
+            Ok(result) => result?,
+            Err(_) => return Err(BiteError::Handshake("no id from the server".into())),
+        };
+
+        let shared = Arc::new(Mutex::new(Shared::new(
+            options
+                .max_in_flight
+                .map_or_else(Requests::new, Requests::with_limit),
+        )));
+
+        let (frames, queue) = mpsc::unbounded_channel();
+
+        Ok(AsyncClient {
+            id,
+            reader: tokio::spawn(read_frames(read, decoder, shared.clone())),
+            writer: tokio::spawn(write_frames(write, queue, options.clone(), shared.clone())),
+            options,
+            shared,
+            frames,
+        })
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().is_closed()
+    }
+
+    // Frames that came addressed to another client id.
+    pub fn mismatched_frames(&self) -> usize {
+        self.shared.lock().unwrap().mismatches()
+    }
+
+    pub async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set(key, value)).await?)
+    }
+
+    pub async fn set_if_none(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set_if_none(key, value)).await?)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.request(Command::get(key)).await? {
+            Response::Value(value) => Ok(Some(value)),
+            Response::Missing => Ok(None),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::delete(key)).await?)
+    }
+
+    pub async fn inc(&self, key: &str) -> Result<u64> {
+        match self.request(Command::inc(key)).await? {
+            Response::Counter(count) => Ok(count),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::append(key, value)).await?)
+    }
+
+    pub async fn keys(&self, key: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        match self.request(Command::keys(key)).await? {
+            Response::Keys(pairs) => Ok(pairs),
+            Response::Missing => Ok(Vec::new()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn json(&self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json(key)).await?)
+    }
+
+    pub async fn json_with_key(&self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json_with_key(key)).await?)
+    }
+
+    pub async fn call(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::call(key, value)).await?)
+    }
+
+    pub async fn subscribe_get(&self, key: &str) -> Result<Subscription> {
+        self.subscribe(Command::subscribe_get(key)).await
+    }
+
+    pub async fn subscribe_keys(&self, key: &str) -> Result<Subscription> {
+        self.subscribe(Command::subscribe_keys(key)).await
+    }
+
+    pub async fn subscribe_json(&self, key: &str) -> Result<Subscription> {
+        self.subscribe(Command::subscribe_json(key)).await
+    }
+
+    // Sends any command and waits for its own reply.
+    pub async fn request(&self, command: Command) -> Result<Response> {
+        let (id, reply) = self.send(command, None)?;
+        self.wait_reply(id, reply).await
+    }
+
+    async fn subscribe(&self, command: Command) -> Result<Subscription> {
+        // Registered before sending, the first update may come right after
+        // the OK.
+        let (sender, events) = mpsc::unbounded_channel();
+
+        let (id, reply) = self.send(command.clone(), Some(sender))?;
+        expect_ok(self.wait_reply(id, reply).await?)?;
+
+        Ok(Subscription {
+            id,
+            command,
+            events,
+            shared: self.shared.clone(),
+        })
+    }
+
+    fn send(
+        &self,
+        command: Command,
+        events: Option<mpsc::UnboundedSender<Notification>>,
+    ) -> Result<(u16, mpsc::UnboundedReceiver<Response>)> {
+        let payload = command.payload()?;
+
+        let (sender, reply) = mpsc::unbounded_channel();
+
+        let id = self.shared.lock().unwrap().begin(command, sender, events)?;
+
+        // Only fails when the writer is gone, then the connection is closed
+        // and the reply never comes.
+        let _ = self.frames.send(Frame::new(self.id, id, payload));
+
+        Ok((id, reply))
+    }
+
+    async fn wait_reply(
+        &self,
+        id: u16,
+        mut reply: mpsc::UnboundedReceiver<Response>,
+    ) -> Result<Response> {
+        match timeout(self.options.read_timeout, reply.recv()).await {
+            Ok(Some(response)) => Ok(response),
+
+            // The connection closed and took the sender with it.
+            Ok(None) => Err(BiteError::Disconnected),
+
+            Err(_) => {
+                self.shared.lock().unwrap().cancel(id);
+                Err(BiteError::Timeout)
+            }
+        }
+    }
+}
+
+impl Drop for AsyncClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.writer.abort();
+    }
+}
+
+impl Subscription {
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.unsubscribe(self.id);
+        }
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Notification;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Notification>> {
+        self.get_mut().events.poll_recv(cx)
+    }
+}
+
+async fn connect(addr: SocketAddr, options: &ConnectOptions) -> io::Result<TcpStream> {
+    let socket = socket(addr, options)?;
+    socket.set_nonblocking(true)?;
+
+    TcpSocket::from_std_stream(socket.into())
+        .connect(addr)
+        .await
+}
+
+async fn handshake(read: &mut OwnedReadHalf, decoder: &mut FrameDecoder) -> Result<u16> {
+    loop {
+        if let Some(id) = greeted(decoder)? {
+            return Ok(id);
+        }
+
+        decoder.buffer_mut().reserve(READ_SIZE);
+
+        if read.read_buf(decoder.buffer_mut()).await? == 0 {
+            return Err(BiteError::Disconnected);
+        }
+    }
+}
+
+async fn read_frames(
+    mut read: OwnedReadHalf,
+    mut decoder: FrameDecoder,
+    shared: Arc<Mutex<Shared>>,
+) {
+    loop {
+        // We lost track of the stream.
+        if !shared.lock().unwrap().dispatch_all(&mut decoder) {
+            return;
+        }
+
+        decoder.buffer_mut().reserve(READ_SIZE);
+
+        match read.read_buf(decoder.buffer_mut()).await {
+            Ok(0) | Err(_) => {
+                shared.lock().unwrap().close();
+                return;
+            }
+
+            Ok(_) => {}
+        }
+    }
+}
+
+// A write that doesn't finish in time leaves the stream in an unknown state,
+// so it closes the connection.
+async fn write_frames(
+    mut write: OwnedWriteHalf,
+    mut queue: mpsc::UnboundedReceiver<Frame>,
+    options: ConnectOptions,
+    shared: Arc<Mutex<Shared>>,
+) {
+    while let Some(frame) = queue.recv().await {
+        // Already checked when queued.
+        let header = match frame.header() {
+            Ok(header) => header,
+            Err(_) => continue,
+        };
+
+        let mut data = Bytes::copy_from_slice(&header).chain(frame.payload);
+
+        match timeout(options.write_timeout, write.write_all_buf(&mut data)).await {
+            Ok(Ok(())) => {}
+
+            _ => {
+                shared.lock().unwrap().close();
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod async_client_tests {
+    use std::{
+        future::poll_fn,
+        io::{ErrorKind, Write},
+        net::TcpListener,
+        pin::Pin,
+        thread,
+    };
+
+    use futures_core::Stream;
+
+    use super::{AsyncClient, Subscription};
+    use crate::{
+        client::Notification,
+        command::Command,
+        error::BiteError,
+        frame::Frame,
+        options::ConnectOptions,
+        response::Response,
+        test_support::{greet, receive},
+    };
+
+    #[cfg(feature = "tls")]
+    use crate::tls::TlsOptions;
+
+    async fn next(subscription: &mut Subscription) -> Option<Notification> {
+        poll_fn(|cx| Pin::new(&mut *subscription).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 3);
+
+            let first = receive(&mut socket);
+            let second = receive(&mut socket);
+
+            // The second one is answered first.
+            for frame in [second, first] {
+                let reply = match &frame.payload[..] {
+                    b"g a" => b"A".to_vec(),
+                    _ => 5u64.to_be_bytes().to_vec(),
+                };
+
+                let reply = Frame::new(3, frame.id, reply);
+                socket.write_all(&reply.encode().unwrap()).unwrap();
+            }
+        });
+
+        let client = AsyncClient::connect(addr).await.unwrap();
+        assert_eq!(client.id(), 3);
+
+        let (value, count) = tokio::join!(client.get("a"), client.inc("b"));
+        assert_eq!(value.unwrap(), Some(b"A".to_vec()));
+        assert_eq!(count.unwrap(), 5);
+
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscription_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let frame = receive(&mut socket);
+            assert_eq!(frame.payload, &b"#g subs"[..]);
+
+            let mut data = Frame::new(1, frame.id, b"OK".to_vec()).encode().unwrap();
+            for event in [&b"ONE"[..], b"TWO"] {
+                data.extend_from_slice(&Frame::new(1, frame.id, event).encode().unwrap());
+            }
+
+            socket.write_all(&data).unwrap();
+        });
+
+        let client = AsyncClient::connect(addr).await.unwrap();
+        let mut subscription = client.subscribe_get("subs").await.unwrap();
+
+        for expected in [&b"ONE"[..], b"TWO"] {
+            let notification = next(&mut subscription).await.unwrap();
+            assert_eq!(notification.command, Command::subscribe_get("subs"));
+            assert_eq!(notification.response, Response::Value(expected.to_vec()));
+        }
+
+        server.join().unwrap();
+
+        // The server hung up, the stream ends and new calls fail.
+        assert!(next(&mut subscription).await.is_none());
+
+        let err = client.get("subs").await.unwrap_err();
+        assert!(err.is_disconnect(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn dropped_subscription() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let frame = receive(&mut socket);
+            let reply = Frame::new(1, frame.id, b"OK".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+        });
+
+        let client = AsyncClient::connect(addr).await.unwrap();
+        let subscription = client.subscribe_get("subs").await.unwrap();
+        assert_eq!(
+            client
+                .shared
+                .lock()
+                .unwrap()
+                .requests
+                .subscriptions()
+                .count(),
+            1
+        );
+
+        drop(subscription);
+        assert_eq!(
+            client
+                .shared
+                .lock()
+                .unwrap()
+                .requests
+                .subscriptions()
+                .count(),
+            0
+        );
+
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn wrong_sender() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            // Someone else's reply comes first.
+            let frame = receive(&mut socket);
+            let mut data = Frame::new(2, frame.id, b"OTHER".to_vec()).encode().unwrap();
+            data.extend_from_slice(&Frame::new(1, frame.id, b"MINE".to_vec()).encode().unwrap());
+            socket.write_all(&data).unwrap();
+        });
+
+        let client = AsyncClient::connect(addr).await.unwrap();
+        assert_eq!(client.get("key").await.unwrap(), Some(b"MINE".to_vec()));
+        assert_eq!(client.mismatched_frames(), 1);
+
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn handshake_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || drop(listener.accept().unwrap()));
+
+        let err = AsyncClient::connect(addr).await.err().unwrap();
+        assert!(matches!(err, BiteError::Disconnected), "{:?}", err);
+
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_unix_socket() {
+        let options = ConnectOptions::new()
+            .addr("127.0.0.1:1984")
+            .unix_socket("/tmp/bite.sock");
+
+        match AsyncClient::connect_with(options).await.err().unwrap() {
+            BiteError::Connect(err) => assert_eq!(err.kind(), ErrorKind::Unsupported),
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn rejects_tls() {
+        let options = ConnectOptions::new()
+            .addr("127.0.0.1:1984")
+            .tls(TlsOptions::new());
+
+        let err = AsyncClient::connect_with(options).await.err().unwrap();
+        assert!(matches!(err, BiteError::Tls(_)), "{:?}", err);
+    }
+}
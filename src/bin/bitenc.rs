@@ -0,0 +1,70 @@
+use std::{
+    env,
+    io::{stdin, stdout, Write},
+    time::Duration,
+};
+
+use bitenc::client::Client;
+use bitenc::command::Command;
+use bitenc::options::{ConnectOptions, DEFAULT_ADDR};
+
+fn main() {
+    // bitenc [host:port]
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string());
+
+    let mut client = match Client::connect_with(ConnectOptions::new().addr(addr)) {
+        Ok(client) => client,
+
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    client.on_reconnect(|event| println!("\n{}", event));
+
+    println!("Connected as {}", client.id());
+
+    loop {
+        let mut input = String::new();
+
+        print!("\n> ");
+        stdout().flush().unwrap();
+
+        // Nothing more to read, the input was closed.
+        if stdin().read_line(&mut input).unwrap() == 0 {
+            println!();
+            break;
+        }
+
+        let input = input.trim();
+
+        if input.is_empty() {
+            continue;
+        }
+
+        let command = match Command::parse(input.as_bytes()) {
+            Some(command) => command,
+
+            None => {
+                println!("Unknown command");
+                continue;
+            }
+        };
+
+        match client.request(command) {
+            Ok(response) => println!("{}", response),
+            Err(err) => println!("{}", err),
+        }
+
+        while let Ok(Some(notification)) = client.next_notification(Duration::ZERO) {
+            println!(
+                "# {} {}: {}",
+                notification.command.verb(),
+                notification.command.key(),
+                notification.response
+            );
+        }
+    }
+}
@@ -0,0 +1,1099 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    command::Command,
+    connection::Connection,
+    error::{BiteError, Result},
+    frame::Frame,
+    idle::{IdlePolicy, PROBE_KEY},
+    options::ConnectOptions,
+    pipeline::Pipeline,
+    reconnect::{ReconnectEvent, ReconnectPolicy},
+    requests::{Requests, Routed},
+    response::Response,
+    transport::Transport,
+};
+
+// Something a subscription sent because the key changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub command: Command,
+    pub response: Response,
+}
+
+type ReconnectHook = Box<dyn FnMut(&ReconnectEvent) + Send>;
+
+pub struct Client {
+    conn: Connection,
+    options: ConnectOptions,
+    requests: Requests,
+    replies: HashMap<u16, Response>,
+    notifications: VecDeque<Notification>,
+    resubscribing: HashSet<u16>,
+    policy: ReconnectPolicy,
+    hook: Option<ReconnectHook>,
+    idle: Option<IdlePolicy>,
+    probe: Option<(u16, Instant)>,
+}
+
+impl Client {
+    // Default options on this address, resolved again on every connect.
+    pub fn connect(addr: impl ToString) -> Result<Client> {
+        Client::connect_with(ConnectOptions::new().addr(addr))
+    }
+
+    pub fn connect_with(options: ConnectOptions) -> Result<Client> {
+        let conn = Connection::open(&options)?;
+
+        Ok(Client::new(conn, options, ReconnectPolicy::default()))
+    }
+
+    // Over anything else, like one end of a pipe. There's nowhere to
+    // reconnect to, so it doesn't.
+    pub fn with_transport(transport: impl Transport + 'static) -> Result<Client> {
+        let options = ConnectOptions::new();
+
+        let mut conn = Connection::with_transport(0, transport);
+        conn.handshake(Instant::now() + options.connect_timeout)?;
+
+        Ok(Client::new(conn, options, ReconnectPolicy::never()))
+    }
+
+    fn new(conn: Connection, options: ConnectOptions, policy: ReconnectPolicy) -> Client {
+        let requests = options
+            .max_in_flight
+            .map_or_else(Requests::new, Requests::with_limit);
+
+        Client {
+            conn,
+            options,
+            requests,
+            replies: HashMap::new(),
+            notifications: VecDeque::new(),
+            resubscribing: HashSet::new(),
+            policy,
+            hook: None,
+            idle: None,
+            probe: None,
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        self.conn.id as u16
+    }
+
+    // The last read or write failed and reconnecting didn't fix it.
+    pub fn is_closed(&self) -> bool {
+        self.conn.closed
+    }
+
+    // How long each call waits for its reply.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.options.read_timeout = timeout;
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    // Off by default, without it a silent dead connection is only noticed
+    // when TCP gives up on its own.
+    pub fn set_idle_policy(&mut self, idle: Option<IdlePolicy>) {
+        self.idle = idle;
+    }
+
+    // Called on every step of a reconnection, from the thread that's using
+    // the client.
+    pub fn on_reconnect(&mut self, hook: impl FnMut(&ReconnectEvent) + Send + 'static) {
+        self.hook = Some(Box::new(hook));
+    }
+
+    pub fn set(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set(key, value))?)
+    }
+
+    pub fn set_if_none(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set_if_none(key, value))?)
+    }
+
+    pub fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.request(Command::get(key))? {
+            Response::Value(value) => Ok(Some(value)),
+            Response::Missing => Ok(None),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::delete(key))?)
+    }
+
+    pub fn inc(&mut self, key: &str) -> Result<u64> {
+        match self.request(Command::inc(key))? {
+            Response::Counter(count) => Ok(count),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn append(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::append(key, value))?)
+    }
+
+    pub fn keys(&mut self, key: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        match self.request(Command::keys(key))? {
+            Response::Keys(pairs) => Ok(pairs),
+            Response::Missing => Ok(Vec::new()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn json(&mut self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json(key))?)
+    }
+
+    pub fn json_with_key(&mut self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json_with_key(key))?)
+    }
+
+    pub fn call(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::call(key, value))?)
+    }
+
+    pub fn subscribe_get(&mut self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::subscribe_get(key))?)
+    }
+
+    pub fn subscribe_keys(&mut self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::subscribe_keys(key))?)
+    }
+
+    pub fn subscribe_json(&mut self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::subscribe_json(key))?)
+    }
+
+    // Any reply to the probe means the server is there.
+    pub fn ping(&mut self) -> Result<()> {
+        self.request(Command::get(PROBE_KEY)).map(|_| ())
+    }
+
+    // Waits up to timeout for something from the subscriptions.
+    pub fn next_notification(&mut self, timeout: Duration) -> Result<Option<Notification>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(notification) = self.notifications.pop_front() {
+                return Ok(Some(notification));
+            }
+
+            match self.read_frame(deadline) {
+                Ok(frame) => self.dispatch(frame),
+                Err(BiteError::Timeout) => return Ok(None),
+                Err(err) if err.is_disconnect() => self.reconnect(err)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Collects commands to send in one go, see Pipeline.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+
+    // Sends any command and blocks until its own reply arrives.
+    pub fn request(&mut self, command: Command) -> Result<Response> {
+        let id = self.send(command)?;
+        self.wait_reply(id)
+    }
+
+    fn send(&mut self, command: Command) -> Result<u16> {
+        Ok(self.send_all(vec![command])?[0])
+    }
+
+    // Queues every command and writes them together. Nothing is sent when
+    // one of them is invalid or too big, or there aren't enough free message
+    // ids.
+    fn send_all(&mut self, commands: Vec<Command>) -> Result<Vec<u16>> {
+        self.send_with(commands, Requests::begin)
+    }
+
+    fn send_with(
+        &mut self,
+        commands: Vec<Command>,
+        begin: fn(&mut Requests, Command) -> Option<u16>,
+    ) -> Result<Vec<u16>> {
+        let mut payloads = Vec::with_capacity(commands.len());
+
+        for command in &commands {
+            payloads.push(command.payload()?);
+        }
+
+        // Whatever closed it may have left half a frame on the stream.
+        if self.conn.closed {
+            self.reconnect(BiteError::Disconnected)?;
+        }
+
+        let mut ids = Vec::with_capacity(commands.len());
+
+        for command in commands {
+            match begin(&mut self.requests, command) {
+                Some(id) => ids.push(id),
+
+                None => {
+                    self.cancel_all(&ids);
+                    return Err(BiteError::Busy);
+                }
+            }
+        }
+
+        let from = self.id();
+
+        for (&id, payload) in ids.iter().zip(payloads) {
+            self.conn.queue_frame(&Frame::new(from, id, payload))?;
+        }
+
+        // Reconnecting sends every pending request again, these too.
+        let result = match self.conn.flush(Instant::now() + self.options.write_timeout) {
+            Err(err) if err.is_disconnect() => self.reconnect(err),
+
+            // The rest can't go out later, the server may be in the middle
+            // of a frame already.
+            Err(BiteError::Timeout) => {
+                self.conn.send_queue.clear();
+                self.conn.closed = true;
+                self.reconnect(BiteError::Timeout)
+            }
+
+            result => result,
+        };
+
+        match result {
+            Ok(()) => Ok(ids),
+
+            Err(err) => {
+                self.cancel_all(&ids);
+                Err(err)
+            }
+        }
+    }
+
+    // Sends the commands at once, then waits for the replies in order.
+    pub(crate) fn request_all(&mut self, commands: Vec<Command>) -> Result<Vec<Response>> {
+        let ids = self.send_all(commands)?;
+        let mut responses = Vec::with_capacity(ids.len());
+
+        for (index, &id) in ids.iter().enumerate() {
+            match self.wait_reply(id) {
+                Ok(response) => responses.push(response),
+
+                Err(err) => {
+                    self.cancel_all(&ids[index + 1..]);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(responses)
+    }
+
+    // Forgets the requests, and any reply that already arrived for them.
+    fn cancel_all(&mut self, ids: &[u16]) {
+        for id in ids {
+            self.requests.cancel(*id);
+            self.replies.remove(id);
+        }
+    }
+
+    fn wait_reply(&mut self, id: u16) -> Result<Response> {
+        let mut deadline = Instant::now() + self.options.read_timeout;
+
+        loop {
+            if let Some(response) = self.replies.remove(&id) {
+                return Ok(response);
+            }
+
+            // A connection that lost track of the stream is as good as gone.
+            let result = match self.read_frame(deadline) {
+                Err(err) if err.is_disconnect() || self.conn.closed => match self.reconnect(err) {
+                    // The request was sent again, it gets a fresh deadline.
+                    Ok(()) => {
+                        deadline = Instant::now() + self.options.read_timeout;
+                        continue;
+                    }
+
+                    Err(err) => Err(err),
+                },
+
+                result => result,
+            };
+
+            match result {
+                Ok(frame) => self.dispatch(frame),
+
+                Err(err) => {
+                    // A late reply will be unknown and ignored.
+                    self.requests.cancel(id);
+
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // Like Connection::read_frame, but wakes up when the idle policy needs
+    // to probe or to check on a probe.
+    fn read_frame(&mut self, deadline: Instant) -> Result<Frame> {
+        loop {
+            let wake = match self.check_idle()? {
+                Some(check) => check.min(deadline),
+                None => deadline,
+            };
+
+            match self.conn.read_frame(wake) {
+                Err(BiteError::Timeout) if wake < deadline => continue,
+                result => return result,
+            }
+        }
+    }
+
+    // Sends the probe after too long without hearing from the server, and
+    // fails when the probe gets no answer in time. Returns when to check
+    // again.
+    fn check_idle(&mut self) -> Result<Option<Instant>> {
+        let idle = match self.idle.clone() {
+            Some(idle) => idle,
+            None => return Ok(None),
+        };
+
+        let now = Instant::now();
+
+        if let Some((_, sent)) = self.probe {
+            if self.conn.last_read >= sent {
+                self.clear_probe();
+            } else if now >= sent + idle.dead_after {
+                self.clear_probe();
+                self.conn.closed = true;
+
+                return Err(BiteError::Disconnected);
+            } else {
+                return Ok(Some(sent + idle.dead_after));
+            }
+        }
+
+        let probe_at = self.conn.last_read + idle.probe_after;
+
+        if now < probe_at {
+            return Ok(Some(probe_at));
+        }
+
+        // It isn't one of the caller's requests, max_in_flight doesn't apply.
+        let id = self.send_with(vec![Command::get(PROBE_KEY)], Requests::begin_unlimited)?[0];
+        self.probe = Some((id, now));
+
+        Ok(Some(now + idle.dead_after))
+    }
+
+    // Once we stop waiting for the probe its reply is nobody's, and it
+    // isn't sent again on a new connection either.
+    fn clear_probe(&mut self) {
+        if let Some((id, _)) = self.probe.take() {
+            self.requests.cancel(id);
+        }
+    }
+
+    // Opens a new connection following the policy, then sends again every
+    // request that didn't get its reply, and every subscription. Requests
+    // may run twice on the server if it got them before the connection
+    // dropped.
+    fn reconnect(&mut self, cause: BiteError) -> Result<()> {
+        self.emit(ReconnectEvent::Disconnected {
+            reason: cause.to_string(),
+        });
+
+        let mut attempt = 1;
+        let mut restored = 0;
+
+        while self.policy.allows(attempt) {
+            let delay = self.policy.delay(attempt);
+            self.emit(ReconnectEvent::Reconnecting { attempt, delay });
+            sleep(delay);
+
+            if let Ok(conn) = Connection::open(&self.options) {
+                self.conn = conn;
+                self.clear_probe();
+
+                // Restored subscriptions stay pending, a later attempt sends
+                // them again.
+                let ids = self.requests.resubscribe();
+                restored += ids.len();
+                self.resubscribing.extend(ids);
+
+                // Lost it again before everything went out, that's one more
+                // failed attempt.
+                if self.resend().is_ok() {
+                    self.emit(ReconnectEvent::Reconnected {
+                        id: self.id(),
+                        attempts: attempt,
+                    });
+
+                    if restored > 0 {
+                        self.emit(ReconnectEvent::Resubscribed { count: restored });
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            attempt += 1;
+        }
+
+        self.emit(ReconnectEvent::GaveUp {
+            attempts: attempt - 1,
+        });
+
+        Err(cause)
+    }
+
+    fn resend(&mut self) -> Result<()> {
+        let from = self.id();
+
+        for (id, command) in self.requests.pending() {
+            self.conn
+                .queue_frame(&Frame::new(from, id, command.to_bytes()))?;
+        }
+
+        self.conn.flush(Instant::now() + self.options.write_timeout)
+    }
+
+    fn emit(&mut self, event: ReconnectEvent) {
+        if let Some(hook) = self.hook.as_mut() {
+            hook(&event);
+        }
+    }
+
+    fn dispatch(&mut self, frame: Frame) {
+        match self.requests.route(frame) {
+            // Nobody waits for the answer to a restored subscription, only
+            // a refusal is worth telling.
+            Routed::Reply {
+                id,
+                command,
+                response,
+            } if self.resubscribing.remove(&id) => {
+                if response != Response::Ok {
+                    self.emit(ReconnectEvent::ResubscribeFailed { command, response });
+                }
+            }
+
+            // Nor for the answer to a probe, it only needs to arrive.
+            Routed::Reply { id, .. } if self.probe.is_some_and(|(probe, _)| probe == id) => {
+                self.probe = None;
+            }
+
+            Routed::Reply { id, response, .. } => {
+                self.replies.insert(id, response);
+            }
+
+            Routed::Event {
+                command, response, ..
+            } => {
+                self.notifications
+                    .push_back(Notification { command, response });
+            }
+
+            Routed::Unknown(_) => {}
+        }
+    }
+}
+
+pub(crate) fn expect_ok(response: Response) -> Result<()> {
+    match response {
+        Response::Ok => Ok(()),
+        response => Err(unexpected(response)),
+    }
+}
+
+pub(crate) fn expect_json(response: Response) -> Result<Option<String>> {
+    match response {
+        Response::Json(json) => Ok(Some(json)),
+        Response::Missing => Ok(None),
+        response => Err(unexpected(response)),
+    }
+}
+
+pub(crate) fn unexpected(response: Response) -> BiteError {
+    match response {
+        Response::No => BiteError::No,
+        response => BiteError::Protocol(format!("unexpected reply {:?}", response)),
+    }
+}
+
+#[cfg(test)]
+mod client_tests {
+    use std::{
+        io::Write,
+        net::TcpListener,
+        sync::{mpsc::channel, Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::Client;
+    use crate::{
+        command::Command,
+        connection::Connection,
+        error::BiteError,
+        frame::Frame,
+        idle::IdlePolicy,
+        options::ConnectOptions,
+        reconnect::{ReconnectEvent, ReconnectPolicy},
+        response::Response,
+        test_support::{greet, receive},
+        transport::pipe,
+    };
+
+    #[test]
+    fn reconnects_and_resends() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            // The first connection dies as soon as it gets a request.
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+            receive(&mut socket);
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 2);
+
+            let frame = receive(&mut socket);
+            assert_eq!(frame.from, 2);
+            assert_eq!(frame.payload, &b"s key value"[..]);
+
+            let reply = Frame::new(2, frame.id, b"OK".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+        });
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook_events = events.clone();
+
+        let mut client = Client::connect(addr).unwrap();
+        assert_eq!(client.id(), 1);
+
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            jitter: false,
+            ..ReconnectPolicy::default()
+        });
+        client.on_reconnect(move |event| hook_events.lock().unwrap().push(event.clone()));
+
+        client.set("key", b"value").unwrap();
+        assert_eq!(client.id(), 2);
+
+        server.join().unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(matches!(events[0], ReconnectEvent::Disconnected { .. }));
+        assert_eq!(
+            events[1..],
+            [
+                ReconnectEvent::Reconnecting {
+                    attempt: 1,
+                    delay: Duration::from_millis(10)
+                },
+                ReconnectEvent::Reconnected { id: 2, attempts: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn resends_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+            receive(&mut socket);
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 2);
+
+            let mut data = Vec::new();
+
+            for i in 0..40 {
+                let frame = receive(&mut socket);
+                assert_eq!(frame.payload, format!("s k {}", i).as_bytes());
+
+                let reply = Frame::new(2, frame.id, b"OK".to_vec());
+                data.extend_from_slice(&reply.encode().unwrap());
+            }
+
+            socket.write_all(&data).unwrap();
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            jitter: false,
+            ..ReconnectPolicy::default()
+        });
+
+        let pipeline = (0..40).fold(client.pipeline(), |pipeline, i| {
+            pipeline.set("k", i.to_string())
+        });
+
+        assert_eq!(pipeline.execute().unwrap(), vec![Response::Ok; 40]);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn resubscribes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let subscription = receive(&mut socket);
+            let reply = Frame::new(1, subscription.id, b"OK".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 2);
+
+            let frame = receive(&mut socket);
+            assert_eq!(frame.id, subscription.id);
+            assert_eq!(frame.payload, &b"#g subs"[..]);
+
+            let mut data = Frame::new(2, frame.id, b"OK".to_vec()).encode().unwrap();
+            let event = Frame::new(2, frame.id, b"AFTER".to_vec());
+            data.extend_from_slice(&event.encode().unwrap());
+            socket.write_all(&data).unwrap();
+        });
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook_events = events.clone();
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            ..ReconnectPolicy::default()
+        });
+        client.on_reconnect(move |event| hook_events.lock().unwrap().push(event.clone()));
+
+        client.subscribe_get("subs").unwrap();
+
+        let notification = client
+            .next_notification(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(notification.command, Command::subscribe_get("subs"));
+        assert_eq!(notification.response, Response::Value(b"AFTER".to_vec()));
+
+        server.join().unwrap();
+
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&ReconnectEvent::Resubscribed { count: 1 })
+        );
+    }
+
+    #[test]
+    fn resubscribe_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (done, wait) = channel();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let subscription = receive(&mut socket);
+            let reply = Frame::new(1, subscription.id, b"OK".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 2);
+
+            let frame = receive(&mut socket);
+            let reply = Frame::new(2, frame.id, b"NO".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+
+            // Stay up until the client is done.
+            wait.recv().unwrap();
+        });
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook_events = events.clone();
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            ..ReconnectPolicy::default()
+        });
+        client.on_reconnect(move |event| hook_events.lock().unwrap().push(event.clone()));
+
+        client.subscribe_get("subs").unwrap();
+
+        let notification = client
+            .next_notification(Duration::from_millis(200))
+            .unwrap();
+
+        assert_eq!(notification, None);
+
+        done.send(()).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&ReconnectEvent::ResubscribeFailed {
+                command: Command::subscribe_get("subs"),
+                response: Response::No
+            })
+        );
+    }
+
+    #[test]
+    fn keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            // Answers the first probe, ignores the second one.
+            let probe = receive(&mut socket);
+            assert_eq!(probe.payload, &b"g bitenc.keepalive"[..]);
+            let reply = Frame::new(1, probe.id, Vec::new());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+
+            receive(&mut socket);
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy::never());
+        client.set_idle_policy(Some(IdlePolicy {
+            probe_after: Duration::from_millis(50),
+            dead_after: Duration::from_millis(100),
+        }));
+
+        let err = client
+            .next_notification(Duration::from_secs(1))
+            .unwrap_err();
+        assert!(err.is_disconnect(), "{:?}", err);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn probe_over_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            // Slow enough for a probe to go out while the get waits.
+            let get = receive(&mut socket);
+            let probe = receive(&mut socket);
+            assert_eq!(probe.payload, &b"g bitenc.keepalive"[..]);
+
+            let mut data = Frame::new(1, probe.id, Vec::new()).encode().unwrap();
+            let reply = Frame::new(1, get.id, b"VALUE".to_vec());
+            data.extend_from_slice(&reply.encode().unwrap());
+            socket.write_all(&data).unwrap();
+        });
+
+        let options = ConnectOptions::new().addr(addr).max_in_flight(1);
+        let mut client = Client::connect_with(options).unwrap();
+        client.set_idle_policy(Some(IdlePolicy {
+            probe_after: Duration::from_millis(20),
+            dead_after: Duration::from_secs(1),
+        }));
+
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn probe_reply_after_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let subscription = receive(&mut socket);
+            let reply = Frame::new(1, subscription.id, b"OK".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+
+            // The probe is answered, but an event gets there first.
+            let probe = receive(&mut socket);
+
+            for (id, payload) in [
+                (subscription.id, &b"ONE"[..]),
+                (probe.id, b""),
+                (subscription.id, b"TWO"),
+            ] {
+                let frame = Frame::new(1, id, payload.to_vec());
+                socket.write_all(&frame.encode().unwrap()).unwrap();
+            }
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_idle_policy(Some(IdlePolicy {
+            probe_after: Duration::from_millis(50),
+            dead_after: Duration::from_secs(1),
+        }));
+
+        client.subscribe_get("key").unwrap();
+
+        for _ in 0..2 {
+            let timeout = Duration::from_secs(1);
+            assert!(client.next_notification(timeout).unwrap().is_some());
+        }
+
+        server.join().unwrap();
+
+        assert!(client.replies.is_empty());
+        assert_eq!(client.requests.in_flight(), 0);
+    }
+
+    #[test]
+    fn pipeline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let frames: Vec<Frame> = (0..3).map(|_| receive(&mut socket)).collect();
+            let payloads: Vec<&[u8]> = frames.iter().map(|frame| &frame.payload[..]).collect();
+            assert_eq!(payloads, [&b"s a 1"[..], b"+1 b", b"g a"]);
+
+            // Out of order, the client sorts them by message id.
+            let replies = [
+                Frame::new(1, frames[2].id, b"1".to_vec()),
+                Frame::new(1, frames[0].id, b"OK".to_vec()),
+                Frame::new(1, frames[1].id, 7u64.to_be_bytes().to_vec()),
+            ];
+
+            for reply in replies {
+                socket.write_all(&reply.encode().unwrap()).unwrap();
+            }
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+
+        let responses = client
+            .pipeline()
+            .set("a", "1")
+            .inc("b")
+            .get("a")
+            .execute()
+            .unwrap();
+
+        assert_eq!(
+            responses,
+            [
+                Response::Ok,
+                Response::Counter(7),
+                Response::Value(b"1".to_vec())
+            ]
+        );
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn abandons_unflushed_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (done, wait) = channel();
+
+        // Never reads, the client fills the socket and gives up.
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+            wait.recv().unwrap();
+        });
+
+        let options = ConnectOptions::new()
+            .addr(addr)
+            .write_timeout(Duration::from_millis(50))
+            .send_buffer_size(4096);
+
+        let mut client = Client::connect_with(options).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy::never());
+
+        let value = vec![b'v'; 60000];
+        let pipeline = (0..200).fold(client.pipeline(), |pipeline, _| {
+            pipeline.set("key", value.clone())
+        });
+
+        assert!(matches!(pipeline.execute(), Err(BiteError::Timeout)));
+        assert!(client.conn.closed);
+        assert!(!client.conn.has_pending_writes());
+
+        // Nothing else goes out on that stream.
+        assert!(matches!(client.get("key"), Err(BiteError::Disconnected)));
+
+        done.send(()).unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn over_pipe() {
+        let (client_end, server_end) = pipe();
+
+        let server = thread::spawn(move || {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let mut conn = Connection::with_transport(0, server_end);
+
+            conn.try_write_frame(&Frame::new(5, 0, Vec::new())).unwrap();
+
+            let frame = conn.read_frame(deadline).unwrap();
+            assert_eq!(frame.payload, &b"g key"[..]);
+
+            conn.try_write_frame(&Frame::new(5, frame.id, b"VALUE".to_vec()))
+                .unwrap();
+        });
+
+        let mut client = Client::with_transport(client_end).unwrap();
+        assert_eq!(client.id(), 5);
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+
+        server.join().unwrap();
+
+        // The other end is gone, and there's no reconnecting a pipe.
+        assert!(client.get("key").unwrap_err().is_disconnect());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn over_unix_socket() {
+        use std::{fs, os::unix::net::UnixListener, process};
+
+        let path = std::env::temp_dir().join(format!("bitenc-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 4);
+
+            let frame = receive(&mut socket);
+            assert_eq!(frame.payload, &b"g key"[..]);
+
+            let reply = Frame::new(4, frame.id, b"VALUE".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+        });
+
+        let options = ConnectOptions::new().unix_socket(&path);
+        let mut client = Client::connect_with(options).unwrap();
+        assert_eq!(client.id(), 4);
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+
+        server.join().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reconnects_when_lost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            // A header smaller than itself, there's no frame after that.
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+            receive(&mut socket);
+            socket.write_all(&[0, 1, 0, 1, 0, 3]).unwrap();
+
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 2);
+
+            let frame = receive(&mut socket);
+            let reply = Frame::new(2, frame.id, b"VALUE".to_vec());
+            socket.write_all(&reply.encode().unwrap()).unwrap();
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            jitter: false,
+            ..ReconnectPolicy::default()
+        });
+
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+        assert_eq!(client.id(), 2);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_key() {
+        let (client_end, server_end) = pipe();
+
+        let server = thread::spawn(move || {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let mut conn = Connection::with_transport(0, server_end);
+
+            conn.try_write_frame(&Frame::new(5, 0, Vec::new())).unwrap();
+
+            // Nothing went out for the bad ones.
+            let frame = conn.read_frame(deadline).unwrap();
+            assert_eq!(frame.payload, &b"g key"[..]);
+
+            conn.try_write_frame(&Frame::new(5, frame.id, Vec::new()))
+                .unwrap();
+        });
+
+        let mut client = Client::with_transport(client_end).unwrap();
+
+        let err = client.set("my key", b"value").unwrap_err();
+        assert!(matches!(err, BiteError::InvalidKey(_)), "{:?}", err);
+
+        let err = client.get("").unwrap_err();
+        assert!(matches!(err, BiteError::InvalidKey(_)), "{:?}", err);
+
+        assert_eq!(client.get("key").unwrap(), None);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn gives_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+        });
+
+        let mut client = Client::connect(addr).unwrap();
+        client.set_reconnect_policy(ReconnectPolicy::never());
+
+        server.join().unwrap();
+
+        let err = client.get("key").unwrap_err();
+        assert!(matches!(err, BiteError::Disconnected), "{:?}", err);
+    }
+}
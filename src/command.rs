@@ -0,0 +1,300 @@
+use crate::{
+    error::{BiteError, Result},
+    frame::MAX_PAYLOAD_SIZE,
+};
+
+// Every instruction BITE understands. Keys are sent as they are, the server
+// splits on the first space so they can't contain one. Values go as raw
+// bytes after the key, so anything binary is fine there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Set { key: String, value: Vec<u8> },
+    SetIfNone { key: String, value: Vec<u8> },
+    Get { key: String },
+    Delete { key: String },
+    Inc { key: String },
+    Append { key: String, value: Vec<u8> },
+    Keys { key: String },
+    Json { key: String },
+    JsonWithKey { key: String },
+    SubscribeGet { key: String },
+    SubscribeKeys { key: String },
+    SubscribeJson { key: String },
+    Call { key: String, value: Vec<u8> },
+}
+
+impl Command {
+    pub fn set(key: impl Into<String>, value: impl Into<Vec<u8>>) -> Command {
+        Command::Set {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn set_if_none(key: impl Into<String>, value: impl Into<Vec<u8>>) -> Command {
+        Command::SetIfNone {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn get(key: impl Into<String>) -> Command {
+        Command::Get { key: key.into() }
+    }
+
+    pub fn delete(key: impl Into<String>) -> Command {
+        Command::Delete { key: key.into() }
+    }
+
+    pub fn inc(key: impl Into<String>) -> Command {
+        Command::Inc { key: key.into() }
+    }
+
+    pub fn append(key: impl Into<String>, value: impl Into<Vec<u8>>) -> Command {
+        Command::Append {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn keys(key: impl Into<String>) -> Command {
+        Command::Keys { key: key.into() }
+    }
+
+    pub fn json(key: impl Into<String>) -> Command {
+        Command::Json { key: key.into() }
+    }
+
+    pub fn json_with_key(key: impl Into<String>) -> Command {
+        Command::JsonWithKey { key: key.into() }
+    }
+
+    pub fn subscribe_get(key: impl Into<String>) -> Command {
+        Command::SubscribeGet { key: key.into() }
+    }
+
+    pub fn subscribe_keys(key: impl Into<String>) -> Command {
+        Command::SubscribeKeys { key: key.into() }
+    }
+
+    pub fn subscribe_json(key: impl Into<String>) -> Command {
+        Command::SubscribeJson { key: key.into() }
+    }
+
+    pub fn call(key: impl Into<String>, value: impl Into<Vec<u8>>) -> Command {
+        Command::Call {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    // The other way around of to_bytes, for text typed by a person.
+    pub fn parse(bytes: &[u8]) -> Option<Command> {
+        let mut parts = bytes.splitn(3, |&byte| byte == b' ');
+
+        let verb = parts.next()?;
+        let key = parts.next().filter(|key| !key.is_empty())?;
+        let key = String::from_utf8(key.to_vec()).ok()?;
+        let value = parts.next().unwrap_or_default().to_vec();
+
+        let command = match verb {
+            b"s" => Command::Set { key, value },
+            b"s?" => Command::SetIfNone { key, value },
+            b"+" => Command::Append { key, value },
+            b"!" => Command::Call { key, value },
+
+            _ if !value.is_empty() => return None,
+
+            b"g" => Command::Get { key },
+            b"d" => Command::Delete { key },
+            b"+1" => Command::Inc { key },
+            b"k" => Command::Keys { key },
+            b"j" => Command::Json { key },
+            b"js" => Command::JsonWithKey { key },
+            b"#g" => Command::SubscribeGet { key },
+            b"#k" => Command::SubscribeKeys { key },
+            b"#j" => Command::SubscribeJson { key },
+
+            _ => return None,
+        };
+
+        Some(command)
+    }
+
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Command::Set { .. } => "s",
+            Command::SetIfNone { .. } => "s?",
+            Command::Get { .. } => "g",
+            Command::Delete { .. } => "d",
+            Command::Inc { .. } => "+1",
+            Command::Append { .. } => "+",
+            Command::Keys { .. } => "k",
+            Command::Json { .. } => "j",
+            Command::JsonWithKey { .. } => "js",
+            Command::SubscribeGet { .. } => "#g",
+            Command::SubscribeKeys { .. } => "#k",
+            Command::SubscribeJson { .. } => "#j",
+            Command::Call { .. } => "!",
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            Command::Set { key, .. }
+            | Command::SetIfNone { key, .. }
+            | Command::Get { key }
+            | Command::Delete { key }
+            | Command::Inc { key }
+            | Command::Append { key, .. }
+            | Command::Keys { key }
+            | Command::Json { key }
+            | Command::JsonWithKey { key }
+            | Command::SubscribeGet { key }
+            | Command::SubscribeKeys { key }
+            | Command::SubscribeJson { key }
+            | Command::Call { key, .. } => key,
+        }
+    }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        match self {
+            Command::Set { value, .. }
+            | Command::SetIfNone { value, .. }
+            | Command::Append { value, .. }
+            | Command::Call { value, .. } => Some(value),
+
+            _ => None,
+        }
+    }
+
+    pub fn is_subscription(&self) -> bool {
+        matches!(
+            self,
+            Command::SubscribeGet { .. }
+                | Command::SubscribeKeys { .. }
+                | Command::SubscribeJson { .. }
+        )
+    }
+
+    // The text the server expects as the frame payload, "verb key value".
+    // An empty value is left out completely, "s key" sets an empty value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let verb = self.verb().as_bytes();
+        let key = self.key().as_bytes();
+        let value = self.value().unwrap_or_default();
+
+        let mut bytes = Vec::with_capacity(verb.len() + key.len() + value.len() + 2);
+        bytes.extend_from_slice(verb);
+        bytes.push(b' ');
+        bytes.extend_from_slice(key);
+
+        if !value.is_empty() {
+            bytes.push(b' ');
+            bytes.extend_from_slice(value);
+        }
+
+        bytes
+    }
+
+    // to_bytes, checked before it goes in a frame. An empty key, or one
+    // with a space, would reach the server as some other command.
+    pub fn payload(&self) -> Result<Vec<u8>> {
+        let key = self.key();
+
+        if key.is_empty() || key.contains(' ') {
+            return Err(BiteError::InvalidKey(key.to_string()));
+        }
+
+        let payload = self.to_bytes();
+
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(BiteError::PayloadTooLarge(payload.len()));
+        }
+
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use super::Command;
+    use crate::{error::BiteError, frame::MAX_PAYLOAD_SIZE};
+
+    #[test]
+    fn to_bytes() {
+        let cases = [
+            (Command::set("set", "SET"), &b"s set SET"[..]),
+            (Command::set("set", ""), b"s set"),
+            (Command::set_if_none("maybe", "MAYBE"), b"s? maybe MAYBE"),
+            (Command::get("set"), b"g set"),
+            (Command::delete("maybe"), b"d maybe"),
+            (Command::inc("inc"), b"+1 inc"),
+            (Command::append("append", "APP"), b"+ append APP"),
+            (Command::keys("kv"), b"k kv"),
+            (Command::json("j"), b"j j"),
+            (Command::json_with_key("js"), b"js js"),
+            (Command::subscribe_get("subs"), b"#g subs"),
+            (Command::subscribe_keys("subs"), b"#k subs"),
+            (Command::subscribe_json("subs"), b"#j subs"),
+            (Command::call("subs", "CALL"), b"! subs CALL"),
+        ];
+
+        for (command, expected) in cases {
+            assert_eq!(command.to_bytes(), expected, "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let commands = [
+            Command::set("set", "SET"),
+            Command::set("set", ""),
+            Command::set_if_none("maybe", "MAYBE"),
+            Command::get("set"),
+            Command::delete("maybe"),
+            Command::inc("inc"),
+            Command::append("append", "APP END"),
+            Command::keys("kv"),
+            Command::json("j"),
+            Command::json_with_key("js"),
+            Command::subscribe_get("subs"),
+            Command::subscribe_keys("subs"),
+            Command::subscribe_json("subs"),
+            Command::call("subs", "CALL"),
+        ];
+
+        for command in commands {
+            assert_eq!(Command::parse(&command.to_bytes()), Some(command));
+        }
+
+        assert_eq!(Command::parse(b""), None);
+        assert_eq!(Command::parse(b"g"), None);
+        assert_eq!(Command::parse(b"g key extra"), None);
+        assert_eq!(Command::parse(b"x key"), None);
+    }
+
+    #[test]
+    fn payload() {
+        assert_eq!(Command::get("key").payload().unwrap(), b"g key");
+
+        for key in ["", "my key", " "] {
+            let err = Command::set(key, "v").payload().unwrap_err();
+            assert!(matches!(err, BiteError::InvalidKey(_)), "{:?}", err);
+        }
+
+        let err = Command::set("big", vec![0; MAX_PAYLOAD_SIZE]).payload();
+        assert!(matches!(err, Err(BiteError::PayloadTooLarge(_))));
+    }
+
+    #[test]
+    fn binary_value() {
+        let value = vec![0, 32, 255, 10, 13];
+        let command = Command::set("bin", value.clone());
+
+        let mut expected = b"s bin ".to_vec();
+        expected.extend_from_slice(&value);
+
+        assert_eq!(command.to_bytes(), expected);
+    }
+}
@@ -1,91 +1,490 @@
 use std::{
+    collections::VecDeque,
     io::{
         self,
-        ErrorKind::{BrokenPipe, Interrupted, WouldBlock},
-        Read, Write,
+        ErrorKind::{BrokenPipe, Interrupted, NotConnected, WouldBlock},
+        IoSlice,
     },
-    net::{SocketAddr, TcpStream},
+    net::{self, SocketAddr},
     time::Instant,
 };
 
-const BUFFER_SIZE: usize = 4096;
+use bytes::{Buf, Bytes};
+use mio::net::TcpStream;
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+
+use crate::{
+    error::{BiteError, Result},
+    frame::{Frame, FrameDecoder, FrameError},
+    options::ConnectOptions,
+    transport::{TcpTransport, Transport},
+};
+
+#[cfg(feature = "tls")]
+use crate::tls::TlsTransport;
+
+#[cfg(not(unix))]
+use crate::options::unsupported;
+
+#[cfg(unix)]
+use {crate::transport::UnixTransport, std::os::unix::net::UnixStream};
+
+// Buffers handed to each write_vectored, a header and a payload per frame.
+const MAX_SLICES: usize = 64;
 
 pub struct Connection {
     pub id: usize,
-    pub socket: TcpStream,
-    pub addr: SocketAddr,
-    pub send_queue: Vec<Vec<u8>>,
+    pub socket: Box<dyn Transport>,
+    pub addr: Option<SocketAddr>,
+    pub send_queue: VecDeque<Bytes>,
     pub pending_read: bool,
     pub last_read: Instant,
     pub last_write: Instant,
     pub closed: bool,
+    decoder: FrameDecoder,
+    unread: VecDeque<Frame>,
+    writable: bool,
 }
 
 impl Connection {
-    pub fn new(id: usize, socket: TcpStream, addr: SocketAddr) -> Connection {
-        let send_queue = Vec::<Vec<u8>>::new();
+    pub fn new(id: usize, socket: net::TcpStream, addr: SocketAddr) -> Result<Connection> {
+        socket.set_nonblocking(true)?;
+        let transport = TcpTransport::new(TcpStream::from_std(socket))?;
+
+        let mut conn = Connection::with_transport(id, transport);
+        conn.addr = Some(addr);
+
+        Ok(conn)
+    }
+
+    pub fn with_transport(id: usize, transport: impl Transport + 'static) -> Connection {
+        let send_queue = VecDeque::<Bytes>::new();
 
+        // Readiness may be edge-triggered, we only hear about it when it
+        // changes. Until the first event we assume there could be something
+        // to read and room to write, trying costs a WouldBlock at most.
         Connection {
             id,
-            socket,
-            addr,
+            socket: Box::new(transport),
+            addr: None,
             send_queue,
-            pending_read: false,
+            pending_read: true,
             last_read: Instant::now(),
             last_write: Instant::now(),
             closed: false,
+            decoder: FrameDecoder::new(),
+            unread: VecDeque::new(),
+            writable: true,
         }
     }
 
-    pub fn try_read(&mut self) -> io::Result<Vec<u8>> {
-        match read(&mut self.socket) {
-            Ok(data) => Ok(data),
+    // Connects to the socket file if there's one, otherwise to the first
+    // address that answers, with the socket options set before connecting,
+    // and TLS on top when asked. Then waits for our id.
+    pub fn open(options: &ConnectOptions) -> Result<Connection> {
+        let mut conn = match options.unix_socket {
+            #[cfg(unix)]
+            Some(ref path) => {
+                let socket = UnixStream::connect(path).map_err(BiteError::Connect)?;
+                socket.set_nonblocking(true)?;
+
+                let transport = UnixTransport::new(mio::net::UnixStream::from_std(socket))?;
+                Connection::with_transport(0, transport)
+            }
+
+            #[cfg(not(unix))]
+            Some(_) => return Err(unsupported("unix sockets are not supported here")),
+
+            #[cfg(feature = "tls")]
+            _ if options.tls.is_some() => {
+                let tls = options.tls.as_ref().unwrap();
+                let (config, name) = (tls.config()?, tls.name(options)?);
+
+                let socket = connect_any(options)?;
+                let local = socket.local_addr()?;
+                socket.set_nonblocking(true)?;
+
+                let transport = TcpTransport::new(TcpStream::from_std(socket))?;
+
+                let mut conn =
+                    Connection::with_transport(0, TlsTransport::new(transport, config, name)?);
+                conn.addr = Some(local);
+                conn
+            }
+
+            _ => {
+                let socket = connect_any(options)?;
+                let local = socket.local_addr()?;
+
+                Connection::new(0, socket, local)?
+            }
+        };
+
+        conn.handshake(Instant::now() + options.connect_timeout)?;
+
+        Ok(conn)
+    }
+
+    // Reads everything the socket has right now into the decoder, returns
+    // how many bytes that was.
+    pub fn try_read(&mut self) -> Result<usize> {
+        match read(
+            self.socket.as_mut(),
+            &mut self.decoder,
+            &mut self.pending_read,
+        ) {
+            Ok(count) => {
+                if count > 0 {
+                    self.last_read = Instant::now();
+                }
+
+                Ok(count)
+            }
 
             Err(err) => {
                 self.closed = true;
 
-                Err(err)
+                Err(err.into())
+            }
+        }
+    }
+
+    // BITE greets every new client with a header-only frame, message id 0,
+    // stamped with the id we must use from now on. Anything that comes after
+    // it stays in the decoder.
+    pub fn handshake(&mut self, deadline: Instant) -> Result<u16> {
+        let frame = match self.read_frame(deadline) {
+            Ok(frame) => frame,
+
+            Err(BiteError::Timeout) => {
+                return Err(BiteError::Handshake("no id from the server".into()))
+            }
+
+            Err(err) => return Err(err),
+        };
+
+        let id = greeting(&frame)?;
+
+        self.id = id as usize;
+        self.decoder.expect_from(id);
+
+        Ok(id)
+    }
+
+    // Frames that came addressed to another client id.
+    pub fn mismatched_frames(&self) -> usize {
+        self.decoder.mismatches()
+    }
+
+    // Waits for the next complete frame until the deadline. Frames that
+    // arrived together with it stay in the decoder for the next call.
+    pub fn read_frame(&mut self, deadline: Instant) -> Result<Frame> {
+        if let Some(frame) = self.unread.pop_front() {
+            return Ok(frame);
+        }
+
+        loop {
+            match self.decoder.next_frame() {
+                Ok(Some(frame)) => return Ok(frame),
+
+                Ok(None) => {}
+
+                Err(err @ FrameError::WrongSender { .. }) => return Err(err.into()),
+
+                // We lost track of the stream, nothing more can be read from
+                // this connection.
+                Err(err) => {
+                    self.closed = true;
+                    return Err(err.into());
+                }
+            }
+
+            // Replies may be waiting on what we haven't written yet.
+            if self.writable && self.has_pending_writes() {
+                self.try_flush()?;
+            }
+
+            if self.pending_read {
+                self.try_read()?;
+                continue;
+            }
+
+            if !self.wait(deadline)? {
+                return Err(BiteError::Timeout);
+            }
+        }
+    }
+
+    // Frames for another client are skipped here. When it fails half way,
+    // the frames already read go back for the next call.
+    pub fn read_frames(&mut self, count: usize, deadline: Instant) -> Result<Vec<Frame>> {
+        let mut frames = Vec::with_capacity(count);
+
+        while frames.len() < count {
+            match self.read_own_frame(deadline) {
+                Ok(frame) => frames.push(frame),
+
+                Err(err) => {
+                    self.unread(frames);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(frames)
+    }
+
+    // Every frame up to and including the one with this message id, same as
+    // read_frames otherwise.
+    pub fn read_until_id(&mut self, id: u16, deadline: Instant) -> Result<Vec<Frame>> {
+        let mut frames = Vec::new();
+
+        loop {
+            match self.read_own_frame(deadline) {
+                Ok(frame) => {
+                    let found = frame.id == id;
+                    frames.push(frame);
+
+                    if found {
+                        return Ok(frames);
+                    }
+                }
+
+                Err(err) => {
+                    self.unread(frames);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // The decoder counts a mismatch for every frame it skips, that's how we
+    // tell them from the errors that matter.
+    fn read_own_frame(&mut self, deadline: Instant) -> Result<Frame> {
+        loop {
+            let mismatches = self.decoder.mismatches();
+
+            match self.read_frame(deadline) {
+                Err(_) if self.decoder.mismatches() > mismatches => continue,
+                result => return result,
             }
         }
     }
 
-    pub fn try_write(&mut self, data: Vec<u8>) -> io::Result<usize> {
-        match write(&mut self.socket, data) {
-            Ok(count) => Ok(count),
+    fn unread(&mut self, frames: Vec<Frame>) {
+        for frame in frames.into_iter().rev() {
+            self.unread.push_front(frame);
+        }
+    }
+
+    // Sleeps until the socket becomes readable or writable, or the deadline
+    // passes. False when nothing happened in time.
+    fn wait(&mut self, deadline: Instant) -> io::Result<bool> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        if timeout.is_zero() {
+            return Ok(false);
+        }
+
+        let ready = match self.socket.wait(timeout) {
+            Ok(ready) => ready,
+
+            // Got interrupted, the caller will come back if there's time.
+            Err(ref err) if err.kind() == Interrupted => return Ok(true),
+
+            Err(err) => return Err(err),
+        };
+
+        if ready.readable {
+            self.pending_read = true;
+        }
+
+        if ready.writable {
+            self.writable = true;
+        }
+
+        Ok(ready.readable || ready.writable)
+    }
+
+    // Queues the data and writes as much of the queue as the socket takes
+    // right now, the rest waits for the next try_write, try_flush or flush.
+    pub fn try_write(&mut self, data: impl Into<Bytes>) -> Result<usize> {
+        let data = data.into();
+
+        if !data.is_empty() {
+            self.send_queue.push_back(data);
+        }
+
+        self.try_flush()
+    }
+
+    // Queues the header and the payload as they are, without copying the
+    // payload, and without writing yet. Frames queued together go out
+    // together on the next flush.
+    pub fn queue_frame(&mut self, frame: &Frame) -> Result<()> {
+        let header = frame.header()?;
+
+        self.send_queue.push_back(Bytes::copy_from_slice(&header));
+
+        if !frame.payload.is_empty() {
+            self.send_queue.push_back(frame.payload.clone());
+        }
+
+        Ok(())
+    }
+
+    pub fn try_write_frame(&mut self, frame: &Frame) -> Result<usize> {
+        self.queue_frame(frame)?;
+        self.try_flush()
+    }
+
+    pub fn try_flush(&mut self) -> Result<usize> {
+        match write(self.socket.as_mut(), &mut self.send_queue) {
+            Ok(count) => {
+                if count > 0 {
+                    self.last_write = Instant::now();
+                }
+
+                // Something left means the socket said WouldBlock, we'll
+                // hear from the event loop when it has room again.
+                self.writable = self.send_queue.is_empty();
+
+                Ok(count)
+            }
 
             Err(err) => {
                 self.closed = true;
 
-                Err(err)
+                Err(err.into())
             }
         }
     }
+
+    // Blocks until everything queued is written or the deadline passes.
+    pub fn flush(&mut self, deadline: Instant) -> Result<()> {
+        loop {
+            if self.writable {
+                self.try_flush()?;
+            }
+
+            if self.send_queue.is_empty() {
+                return Ok(());
+            }
+
+            // Nobody reads while we wait here, but we still remember that
+            // there is something to read later.
+            if !self.wait(deadline)? && Instant::now() >= deadline {
+                return Err(BiteError::Timeout);
+            }
+        }
+    }
+
+    pub fn has_pending_writes(&self) -> bool {
+        !self.send_queue.is_empty()
+    }
+}
+
+// The id the server gives us, if this is the frame that does it.
+pub(crate) fn greeting(frame: &Frame) -> Result<u16> {
+    if frame.id != 0 || !frame.payload.is_empty() {
+        return Err(BiteError::Handshake(format!(
+            "expected the id frame, got message {} with {} bytes",
+            frame.id,
+            frame.payload.len()
+        )));
+    }
+
+    Ok(frame.from)
 }
 
-fn read(socket: &mut TcpStream) -> io::Result<Vec<u8>> {
-    let mut buffer = Vec::with_capacity(BUFFER_SIZE);
+// For the clients that read on their own: the id once the greeting is in
+// the decoder, which from then on expects it on every frame.
+pub(crate) fn greeted(decoder: &mut FrameDecoder) -> Result<Option<u16>> {
+    let id = match decoder.next_frame()? {
+        Some(frame) => greeting(&frame)?,
+        None => return Ok(None),
+    };
+
+    decoder.expect_from(id);
+
+    Ok(Some(id))
+}
+
+// The first address that takes the connection.
+pub(crate) fn connect_any(options: &ConnectOptions) -> Result<net::TcpStream> {
+    let mut last_err = io::Error::new(NotConnected, "no address answered");
+
+    for addr in options.resolve()? {
+        match connect(addr, options) {
+            Ok(socket) => return Ok(socket),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(BiteError::Connect(last_err))
+}
+
+fn connect(addr: SocketAddr, options: &ConnectOptions) -> io::Result<net::TcpStream> {
+    let socket = socket(addr, options)?;
+    socket.connect_timeout(&addr.into(), options.connect_timeout)?;
+
+    Ok(socket.into())
+}
+
+// A socket with the options applied, ready to connect.
+pub(crate) fn socket(addr: SocketAddr, options: &ConnectOptions) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    socket.set_nodelay(options.nodelay)?;
+
+    if let Some(idle) = options.keepalive {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+    }
+
+    if let Some(size) = options.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+
+    if let Some(size) = options.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+
+    Ok(socket)
+}
+
+// Reads into the decoder until the socket says WouldBlock. Pending tells if
+// the socket may still have something, like the end of the stream after the
+// last bytes.
+fn read(
+    socket: &mut dyn Transport,
+    decoder: &mut FrameDecoder,
+    pending: &mut bool,
+) -> io::Result<usize> {
+    let mut total_read = 0;
 
     loop {
-        let mut chunk = vec![0; BUFFER_SIZE];
+        match decoder.read_from(socket) {
+            Ok(0) if total_read > 0 => {
+                // The other side is done, but first the caller gets what
+                // came before, the next read finds the end again.
+                *pending = true;
+                break;
+            }
 
-        match socket.read(&mut chunk) {
             Ok(0) => {
                 // Reading 0 bytes means the other side has closed the
                 // connection or is done writing, then so are we.
                 return Err(BrokenPipe.into());
             }
 
-            Ok(n) => {
-                buffer.extend_from_slice(&chunk[..n]);
-
-                if n < BUFFER_SIZE {
-                    break;
-                }
-            }
+            Ok(n) => total_read += n,
 
             // Would block "errors" are the OS's way of saying that the
             // connection is not actually ready to perform this I/O operation.
-            Err(ref err) if err.kind() == WouldBlock => break,
+            Err(ref err) if err.kind() == WouldBlock => {
+                *pending = false;
+                break;
+            }
 
             // Got interrupted, we'll try again.
             Err(ref err) if err.kind() == Interrupted => continue,
@@ -95,25 +494,38 @@ fn read(socket: &mut TcpStream) -> io::Result<Vec<u8>> {
         }
     }
 
-    Ok(buffer)
+    Ok(total_read)
 }
 
-fn write(socket: &mut TcpStream, data: Vec<u8>) -> io::Result<usize> {
+// Writes as many queued buffers as fit in one write_vectored per syscall,
+// partially written buffers keep their rest at the front of the queue.
+fn write(socket: &mut dyn Transport, queue: &mut VecDeque<Bytes>) -> io::Result<usize> {
     let mut total_written = 0;
 
-    while total_written < data.len() {
-        match socket.write(&data[total_written..]) {
+    while !queue.is_empty() {
+        let mut slices = [IoSlice::new(&[]); MAX_SLICES];
+        let count = queue.len().min(MAX_SLICES);
+
+        for (slice, data) in slices.iter_mut().zip(queue.iter()) {
+            *slice = IoSlice::new(data);
+        }
+
+        match socket.write_vectored(&slices[..count]) {
             Ok(0) => {
                 // Writing 0 bytes means the other side has closed the
                 // connection or is done writing, then so are we.
                 return Err(BrokenPipe.into());
             }
 
-            Ok(n) => total_written += n,
+            Ok(n) => {
+                total_written += n;
+                advance(queue, n);
+            }
 
             // Would block "errors" are the OS's way of saying that the
             // connection is not actually ready to perform this I/O operation.
-            Err(ref err) if err.kind() == WouldBlock => return Err(WouldBlock.into()),
+            // What's left stays queued.
+            Err(ref err) if err.kind() == WouldBlock => break,
 
             // Got interrupted, we'll try again.
             Err(ref err) if err.kind() == Interrupted => continue,
@@ -125,3 +537,258 @@ fn write(socket: &mut TcpStream, data: Vec<u8>) -> io::Result<usize> {
 
     Ok(total_written)
 }
+
+fn advance(queue: &mut VecDeque<Bytes>, mut written: usize) {
+    while let Some(data) = queue.front_mut() {
+        if written < data.len() {
+            data.advance(written);
+            return;
+        }
+
+        written -= data.len();
+        queue.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod connection_tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        time::{Duration, Instant},
+    };
+
+    use super::Connection;
+    use crate::{error::BiteError, frame::Frame};
+
+    fn pair() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        socket.set_nonblocking(true).unwrap();
+
+        let addr = socket.local_addr().unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        (Connection::new(0, socket, addr).unwrap(), server)
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(1)
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut conn, mut server) = pair();
+
+        let mut data = Frame::new(7, 0, Vec::new()).encode().unwrap();
+        data.extend_from_slice(&Frame::new(7, 1, b"OK".to_vec()).encode().unwrap());
+        server.write_all(&data).unwrap();
+
+        assert_eq!(conn.handshake(deadline()).unwrap(), 7);
+        assert_eq!(conn.id, 7);
+
+        // The reply that came glued to the id frame isn't lost.
+        assert_eq!(
+            conn.read_frame(deadline()).unwrap(),
+            Frame::new(7, 1, b"OK".to_vec())
+        );
+    }
+
+    #[test]
+    fn wrong_sender() {
+        let (mut conn, mut server) = pair();
+
+        let mut data = Frame::new(7, 0, Vec::new()).encode().unwrap();
+        data.extend_from_slice(&Frame::new(8, 1, b"OK".to_vec()).encode().unwrap());
+        data.extend_from_slice(&Frame::new(7, 2, b"OK".to_vec()).encode().unwrap());
+        server.write_all(&data).unwrap();
+
+        conn.handshake(deadline()).unwrap();
+
+        let err = conn.read_frame(deadline()).unwrap_err();
+        assert!(matches!(err, BiteError::Protocol(_)), "{:?}", err);
+        assert_eq!(conn.mismatched_frames(), 1);
+
+        assert_eq!(conn.read_frame(deadline()).unwrap().id, 2);
+    }
+
+    #[test]
+    fn lost_stream() {
+        let (mut conn, mut server) = pair();
+
+        server.write_all(&[0, 1, 0, 1, 0, 3]).unwrap();
+
+        let err = conn.read_frame(deadline()).unwrap_err();
+        assert!(matches!(err, BiteError::Protocol(_)), "{:?}", err);
+        assert!(conn.closed);
+    }
+
+    #[test]
+    fn read_until_id() {
+        let (mut conn, mut server) = pair();
+
+        let mut data = Frame::new(7, 0, Vec::new()).encode().unwrap();
+
+        for (from, id) in [(7, 1), (8, 2), (7, 3), (7, 4)] {
+            data.extend_from_slice(&Frame::new(from, id, b"OK".to_vec()).encode().unwrap());
+        }
+
+        server.write_all(&data).unwrap();
+        conn.handshake(deadline()).unwrap();
+
+        let ids = |frames: Vec<Frame>| frames.iter().map(|frame| frame.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(conn.read_until_id(3, deadline()).unwrap()), [1, 3]);
+        assert_eq!(conn.mismatched_frames(), 1);
+
+        // Nothing with id 9 comes, what came before it isn't lost.
+        let soon = Instant::now() + Duration::from_millis(50);
+        let err = conn.read_until_id(9, soon).unwrap_err();
+        assert!(matches!(err, BiteError::Timeout), "{:?}", err);
+
+        assert_eq!(ids(conn.read_frames(1, deadline()).unwrap()), [4]);
+    }
+
+    #[test]
+    fn queued_writes() {
+        let (mut conn, mut server) = pair();
+
+        // Far more than the socket buffers take without anyone reading.
+        let frames: Vec<Vec<u8>> = (0..64u8).map(|i| vec![i; 64 * 1024]).collect();
+        let total: usize = frames.iter().map(|frame| frame.len()).sum();
+
+        let mut written = 0;
+        for frame in frames.clone() {
+            written += conn.try_write(frame).unwrap();
+        }
+
+        assert!(written < total);
+        assert!(conn.has_pending_writes());
+
+        let reader = std::thread::spawn(move || {
+            let mut data = vec![0; total];
+            server.read_exact(&mut data).unwrap();
+            data
+        });
+
+        conn.flush(Instant::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(!conn.has_pending_writes());
+
+        assert_eq!(reader.join().unwrap(), frames.concat());
+    }
+
+    #[test]
+    fn coalesced_frames() {
+        let (mut conn, mut server) = pair();
+
+        let frames: Vec<Frame> = (1..=200)
+            .map(|id| Frame::new(7, id, vec![id as u8; id as usize * 100]))
+            .collect();
+
+        for frame in &frames {
+            conn.queue_frame(frame).unwrap();
+        }
+
+        let expected: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.encode().unwrap())
+            .collect();
+        let total = expected.len();
+
+        let reader = std::thread::spawn(move || {
+            let mut data = vec![0; total];
+            server.read_exact(&mut data).unwrap();
+            data
+        });
+
+        conn.flush(Instant::now() + Duration::from_secs(10))
+            .unwrap();
+
+        assert_eq!(reader.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn flush_timeout() {
+        let (mut conn, _server) = pair();
+
+        conn.try_write(vec![0; 16 * 1024 * 1024]).unwrap();
+
+        let err = conn
+            .flush(Instant::now() + Duration::from_millis(50))
+            .unwrap_err();
+
+        assert!(matches!(err, BiteError::Timeout), "{:?}", err);
+        assert!(conn.has_pending_writes());
+    }
+
+    #[test]
+    fn handshake_split() {
+        let (mut conn, mut server) = pair();
+
+        server.write_all(&[0, 3, 0]).unwrap();
+        server.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        server.write_all(&[0, 0, 6]).unwrap();
+
+        assert_eq!(conn.handshake(deadline()).unwrap(), 3);
+    }
+
+    #[test]
+    fn handshake_wrong_shape() {
+        let (mut conn, mut server) = pair();
+
+        server
+            .write_all(&Frame::new(7, 0, b"OK".to_vec()).encode().unwrap())
+            .unwrap();
+
+        let err = conn.handshake(deadline()).unwrap_err();
+        assert!(matches!(err, BiteError::Handshake(_)), "{:?}", err);
+        assert_eq!(conn.id, 0);
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let (mut conn, mut server) = pair();
+
+        server.write_all(&[0, 3]).unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let err = conn.handshake(deadline).unwrap_err();
+        assert!(matches!(err, BiteError::Handshake(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn handshake_closed() {
+        let (mut conn, server) = pair();
+
+        drop(server);
+
+        let err = conn.handshake(deadline()).unwrap_err();
+        assert!(err.is_disconnect(), "{:?}", err);
+        assert!(conn.closed);
+    }
+
+    #[test]
+    fn frames_before_close() {
+        let (mut conn, mut server) = pair();
+
+        // Many reads worth of frames, then the server hangs up.
+        let mut data = Frame::new(7, 0, Vec::new()).encode().unwrap();
+        for id in 1..=100 {
+            data.extend_from_slice(&Frame::new(7, id, vec![1; 1000]).encode().unwrap());
+        }
+
+        server.write_all(&data).unwrap();
+        drop(server);
+
+        conn.handshake(deadline()).unwrap();
+
+        let frames = conn.read_frames(100, deadline()).unwrap();
+        assert_eq!(frames.last().unwrap().id, 100);
+        assert!(frames.iter().all(|frame| frame.payload.len() == 1000));
+
+        assert!(conn.read_frame(deadline()).unwrap_err().is_disconnect());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+};
+
+use crate::frame::{FrameError, MAX_PAYLOAD_SIZE};
+
+pub type Result<T> = std::result::Result<T, BiteError>;
+
+#[derive(Debug)]
+pub enum BiteError {
+    // Couldn't reach the server at all.
+    Connect(io::Error),
+
+    // The deadline passed before the server answered.
+    Timeout,
+
+    // The server went away, or stopped answering the keepalive probe.
+    Disconnected,
+
+    // The server sent something that isn't BITE, or not what we asked for.
+    Protocol(String),
+
+    // The message doesn't fit in a frame.
+    PayloadTooLarge(usize),
+
+    // Keys can't be empty or have spaces, the server would read another
+    // command.
+    InvalidKey(String),
+
+    // The server replied NO.
+    No,
+
+    // The server didn't greet us with our id.
+    Handshake(String),
+
+    // Every message id is waiting for its reply.
+    Busy,
+
+    // The TLS settings don't work, a bad certificate or key.
+    Tls(String),
+
+    // Anything else the OS told us.
+    Io(io::Error),
+}
+
+impl BiteError {
+    // The connection is gone, a new one may work.
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self, BiteError::Disconnected)
+    }
+}
+
+impl fmt::Display for BiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BiteError::Connect(err) => write!(f, "couldn't connect: {}", err),
+            BiteError::Timeout => write!(f, "timed out waiting for the server"),
+            BiteError::Disconnected => write!(f, "disconnected from the server"),
+            BiteError::Protocol(reason) => write!(f, "protocol violation: {}", reason),
+
+            BiteError::PayloadTooLarge(size) => write!(
+                f,
+                "payload of {} bytes is over the {} bytes limit",
+                size, MAX_PAYLOAD_SIZE
+            ),
+
+            BiteError::InvalidKey(key) => write!(f, "invalid key {:?}", key),
+            BiteError::No => write!(f, "the server replied NO"),
+            BiteError::Handshake(reason) => write!(f, "handshake failed: {}", reason),
+            BiteError::Busy => write!(f, "every message id is in flight"),
+            BiteError::Tls(reason) => write!(f, "TLS: {}", reason),
+            BiteError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for BiteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BiteError::Connect(err) | BiteError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BiteError {
+    fn from(err: io::Error) -> BiteError {
+        match err.kind() {
+            ErrorKind::TimedOut => BiteError::Timeout,
+
+            ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::UnexpectedEof => BiteError::Disconnected,
+
+            _ => BiteError::Io(err),
+        }
+    }
+}
+
+impl From<FrameError> for BiteError {
+    fn from(err: FrameError) -> BiteError {
+        match err {
+            FrameError::PayloadTooLarge(size) => BiteError::PayloadTooLarge(size),
+            err => BiteError::Protocol(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use std::io::{self, ErrorKind};
+
+    use super::BiteError;
+    use crate::frame::FrameError;
+
+    #[test]
+    fn from_io() {
+        let err = BiteError::from(io::Error::from(ErrorKind::ConnectionReset));
+        assert!(err.is_disconnect());
+
+        let err = BiteError::from(io::Error::from(ErrorKind::TimedOut));
+        assert!(matches!(err, BiteError::Timeout));
+
+        let err = BiteError::from(io::Error::from(ErrorKind::PermissionDenied));
+        assert!(matches!(err, BiteError::Io(_)));
+    }
+
+    #[test]
+    fn from_frame() {
+        let err = BiteError::from(FrameError::PayloadTooLarge(70000));
+        assert!(matches!(err, BiteError::PayloadTooLarge(70000)));
+
+        let err = BiteError::from(FrameError::SizeTooSmall(3));
+        assert!(matches!(err, BiteError::Protocol(_)));
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+
+pub const HEADER_SIZE: usize = 6;
+pub const MAX_FRAME_SIZE: usize = u16::MAX as usize;
+pub const MAX_PAYLOAD_SIZE: usize = MAX_FRAME_SIZE - HEADER_SIZE;
+
+// Room made in the decoder for each read.
+pub const READ_SIZE: usize = 16 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    PayloadTooLarge(usize),
+    SizeTooSmall(usize),
+    SizeMismatch { expected: usize, actual: usize },
+    WrongSender { expected: u16, actual: u16 },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::PayloadTooLarge(len) => write!(
+                f,
+                "payload of {} bytes is over the {} bytes limit",
+                len, MAX_PAYLOAD_SIZE
+            ),
+
+            FrameError::SizeTooSmall(size) => write!(
+                f,
+                "frame size {} is smaller than the {} bytes header",
+                size, HEADER_SIZE
+            ),
+
+            FrameError::SizeMismatch { expected, actual } => write!(
+                f,
+                "frame size says {} bytes but got {} bytes",
+                expected, actual
+            ),
+
+            FrameError::WrongSender { expected, actual } => write!(
+                f,
+                "frame addressed to client {} but our id is {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+impl From<FrameError> for io::Error {
+    fn from(err: FrameError) -> io::Error {
+        let kind = match err {
+            FrameError::PayloadTooLarge(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        };
+
+        io::Error::new(kind, err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub from: u16,
+    pub id: u16,
+    pub payload: Bytes,
+}
+
+impl Frame {
+    pub fn new(from: u16, id: u16, payload: impl Into<Bytes>) -> Frame {
+        Frame {
+            from,
+            id,
+            payload: payload.into(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        HEADER_SIZE + self.payload.len()
+    }
+
+    pub fn header(&self) -> Result<[u8; HEADER_SIZE], FrameError> {
+        if self.payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(FrameError::PayloadTooLarge(self.payload.len()));
+        }
+
+        let size = self.size() as u16;
+
+        let [byte0, byte1] = self.from.to_be_bytes();
+        let [byte2, byte3] = self.id.to_be_bytes();
+        let [byte4, byte5] = size.to_be_bytes();
+
+        Ok([byte0, byte1, byte2, byte3, byte4, byte5])
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, FrameError> {
+        let header = self.header()?;
+
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&self.payload);
+
+        Ok(bytes)
+    }
+
+    // Decodes a single, complete frame. Use FrameDecoder for streams.
+    pub fn decode(bytes: &[u8]) -> Result<Frame, FrameError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(FrameError::SizeMismatch {
+                expected: HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+
+        let (from, id, size) = parse_header(bytes)?;
+
+        if size != bytes.len() {
+            return Err(FrameError::SizeMismatch {
+                expected: size,
+                actual: bytes.len(),
+            });
+        }
+
+        Ok(Frame::new(
+            from,
+            id,
+            Bytes::copy_from_slice(&bytes[HEADER_SIZE..]),
+        ))
+    }
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(u16, u16, usize), FrameError> {
+    let from = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let id = u16::from_be_bytes([bytes[2], bytes[3]]);
+    let size = u16::from_be_bytes([bytes[4], bytes[5]]) as usize;
+
+    if size < HEADER_SIZE {
+        return Err(FrameError::SizeTooSmall(size));
+    }
+
+    Ok((from, id, size))
+}
+
+// Accumulates whatever the socket gives us and cuts it into frames. Partial
+// frames stay in the buffer until the rest of their bytes arrive. Payloads
+// are split off the buffer without copying, its memory is reused once every
+// frame that points into it is dropped.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: BytesMut,
+    expected_from: Option<u16>,
+    mismatches: usize,
+}
+
+impl FrameDecoder {
+    pub fn new() -> FrameDecoder {
+        FrameDecoder::default()
+    }
+
+    // Once we know our id, every frame must carry it. Frames for someone
+    // else are consumed, counted and reported as errors.
+    pub fn expect_from(&mut self, from: u16) {
+        self.expected_from = Some(from);
+    }
+
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // Where the socket reads go, straight after the bytes still waiting.
+    pub fn buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.buffer
+    }
+
+    // A single read into the buffer, Ok(0) is the end of the stream.
+    pub fn read_from(&mut self, reader: &mut (impl Read + ?Sized)) -> io::Result<usize> {
+        let start = self.buffer.len();
+
+        // Reserving reclaims the space of the frames already taken when
+        // nothing points into it anymore.
+        self.buffer.reserve(READ_SIZE);
+        self.buffer.resize(start + READ_SIZE, 0);
+
+        let result = reader.read(&mut self.buffer[start..]);
+        let count = *result.as_ref().unwrap_or(&0);
+        self.buffer.truncate(start + count);
+
+        result
+    }
+
+    // A size smaller than the header means we lost track of the stream,
+    // nothing after it can be trusted, so the bad header stays in the buffer
+    // and keeps failing.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        if self.buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let (from, id, size) = parse_header(&self.buffer)?;
+
+        if self.buffer.len() < size {
+            return Ok(None);
+        }
+
+        let mut payload = self.buffer.split_to(size);
+        payload.advance(HEADER_SIZE);
+        let payload = payload.freeze();
+
+        if let Some(expected) = self.expected_from {
+            if from != expected {
+                self.mismatches += 1;
+
+                return Err(FrameError::WrongSender {
+                    expected,
+                    actual: from,
+                });
+            }
+        }
+
+        Ok(Some(Frame::new(from, id, payload)))
+    }
+
+    // Every complete frame so far. Frames for someone else are only counted,
+    // the ones around them still come out.
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, FrameError> {
+        self.push(data);
+
+        let mut frames = Vec::new();
+
+        loop {
+            match self.next_frame() {
+                Ok(Some(frame)) => frames.push(frame),
+
+                Ok(None) => return Ok(frames),
+
+                Err(FrameError::WrongSender { .. }) => {}
+
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Bytes of an incomplete frame waiting for more data.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod frame_tests {
+    use super::{Frame, FrameDecoder, FrameError, MAX_PAYLOAD_SIZE};
+
+    #[test]
+    fn encode() {
+        let frame = Frame::new(0x0102, 0x0304, b"OK".to_vec());
+
+        assert_eq!(frame.encode().unwrap(), &[1, 2, 3, 4, 0, 8, 79, 75]);
+    }
+
+    #[test]
+    fn encode_empty() {
+        let frame = Frame::new(7, 0, Vec::new());
+
+        assert_eq!(frame.encode().unwrap(), &[0, 7, 0, 0, 0, 6]);
+    }
+
+    #[test]
+    fn decode_many() {
+        let mut decoder = FrameDecoder::new();
+
+        let frames = decoder
+            .decode(&[0, 1, 0, 0, 0, 8, 79, 75, 0, 1, 0, 1, 0, 9, 83, 69, 84, 0, 1])
+            .unwrap();
+
+        assert_eq!(
+            frames,
+            vec![
+                Frame::new(1, 0, b"OK".to_vec()),
+                Frame::new(1, 1, b"SET".to_vec())
+            ]
+        );
+        assert_eq!(decoder.pending(), 2);
+    }
+
+    #[test]
+    fn decode_partial() {
+        let frame = Frame::new(3, 42, b"partial payload".to_vec());
+        let bytes = frame.encode().unwrap();
+
+        let mut decoder = FrameDecoder::new();
+        let mut frames = Vec::new();
+
+        for chunk in bytes.chunks(4) {
+            frames.extend(decoder.decode(chunk).unwrap());
+        }
+
+        assert_eq!(frames, vec![frame]);
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn roundtrip_biggest() {
+        let frame = Frame::new(1, 65535, vec![7; MAX_PAYLOAD_SIZE]);
+
+        let mut decoder = FrameDecoder::new();
+        let frames = decoder.decode(&frame.encode().unwrap()).unwrap();
+
+        assert_eq!(frames, vec![frame]);
+    }
+
+    #[test]
+    fn encode_too_large() {
+        let frame = Frame::new(1, 0, vec![0; MAX_PAYLOAD_SIZE + 1]);
+
+        assert_eq!(
+            frame.encode(),
+            Err(FrameError::PayloadTooLarge(MAX_PAYLOAD_SIZE + 1))
+        );
+
+        let frame = Frame::new(1, 0, vec![0; 70 * 1024]);
+
+        assert_eq!(frame.encode(), Err(FrameError::PayloadTooLarge(70 * 1024)));
+    }
+
+    #[test]
+    fn decode_size_too_small() {
+        let mut decoder = FrameDecoder::new();
+
+        assert_eq!(
+            decoder.decode(&[0, 1, 0, 0, 0, 5, 79]),
+            Err(FrameError::SizeTooSmall(5))
+        );
+        assert_eq!(
+            Frame::decode(&[0, 1, 0, 0, 0, 0]),
+            Err(FrameError::SizeTooSmall(0))
+        );
+    }
+
+    #[test]
+    fn wrong_sender() {
+        let mut decoder = FrameDecoder::new();
+        decoder.expect_from(1);
+
+        decoder.push(&[0, 2, 0, 0, 0, 8, 79, 75, 0, 1, 0, 1, 0, 8, 79, 75]);
+
+        assert_eq!(
+            decoder.next_frame(),
+            Err(FrameError::WrongSender {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            decoder.next_frame(),
+            Ok(Some(Frame::new(1, 1, b"OK".to_vec())))
+        );
+        assert_eq!(decoder.mismatches(), 1);
+    }
+
+    #[test]
+    fn decode_skips_wrong_sender() {
+        let mut decoder = FrameDecoder::new();
+        decoder.expect_from(1);
+
+        let frames = decoder
+            .decode(&[0, 1, 0, 1, 0, 8, 79, 75, 0, 2, 0, 2, 0, 6, 0, 1, 0, 3, 0, 6])
+            .unwrap();
+
+        assert_eq!(
+            frames,
+            vec![
+                Frame::new(1, 1, b"OK".to_vec()),
+                Frame::new(1, 3, Vec::new())
+            ]
+        );
+        assert_eq!(decoder.mismatches(), 1);
+    }
+
+    #[test]
+    fn decode_size_mismatch() {
+        assert_eq!(
+            Frame::decode(&[0, 1, 0, 0, 0, 8, 79]),
+            Err(FrameError::SizeMismatch {
+                expected: 8,
+                actual: 7
+            })
+        );
+        assert_eq!(
+            Frame::decode(&[0, 1, 0, 2, 0, 8, 79, 75]),
+            Ok(Frame::new(1, 2, b"OK".to_vec()))
+        );
+    }
+}
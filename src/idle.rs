@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+// The key read to check on a quiet connection. Any reply, even an empty one,
+// proves the server is still there.
+pub const PROBE_KEY: &str = "bitenc.keepalive";
+
+// A TCP connection can die without anyone telling us. After probe_after
+// without receiving anything the client sends a cheap probe, and if nothing
+// arrives in dead_after the connection is considered dead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdlePolicy {
+    pub probe_after: Duration,
+    pub dead_after: Duration,
+}
+
+impl Default for IdlePolicy {
+    fn default() -> IdlePolicy {
+        IdlePolicy {
+            probe_after: Duration::from_secs(30),
+            dead_after: Duration::from_secs(10),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(feature = "tokio")]
+pub mod async_client;
+pub mod client;
+pub mod command;
+pub mod connection;
+pub mod error;
+pub mod frame;
+pub mod idle;
+pub mod options;
+pub mod pipeline;
+pub mod pool;
+pub mod reconnect;
+pub mod requests;
+pub mod response;
+pub mod shared_client;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transport;
+pub mod util;
+
+#[cfg(feature = "tokio")]
+pub use async_client::{AsyncClient, Subscription};
+pub use client::{Client, Notification};
+pub use command::Command;
+pub use connection::Connection;
+pub use error::{BiteError, Result};
+pub use frame::{Frame, FrameDecoder, FrameError};
+pub use idle::IdlePolicy;
+pub use options::ConnectOptions;
+pub use pipeline::Pipeline;
+pub use pool::{Pool, PoolOptions, PoolStats, PooledClient};
+pub use reconnect::{ReconnectEvent, ReconnectPolicy};
+pub use requests::{Requests, Routed};
+pub use response::Response;
+pub use shared_client::SharedClient;
+#[cfg(feature = "tls")]
+pub use tls::{TlsOptions, TlsTransport};
+pub use transport::{pipe, Pipe, Transport};
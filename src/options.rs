@@ -0,0 +1,201 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::error::{BiteError, Result};
+
+#[cfg(feature = "tls")]
+use crate::tls::TlsOptions;
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:1984";
+
+// Where and how to connect. Addresses are resolved again on every connect,
+// so a reconnection follows a name that moved to another host. Each one is
+// tried in order until one answers.
+//
+//     let options = ConnectOptions::new()
+//         .addr("bite.local:1984")
+//         .connect_timeout(Duration::from_secs(1))
+//         .max_in_flight(128);
+//
+//     let client = Client::connect_with(options)?;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectOptions {
+    pub(crate) addrs: Vec<String>,
+    pub(crate) unix_socket: Option<PathBuf>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) read_timeout: Duration,
+    pub(crate) write_timeout: Duration,
+    pub(crate) nodelay: bool,
+    pub(crate) keepalive: Option<Duration>,
+    pub(crate) send_buffer_size: Option<usize>,
+    pub(crate) recv_buffer_size: Option<usize>,
+    pub(crate) max_in_flight: Option<usize>,
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<TlsOptions>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> ConnectOptions {
+        ConnectOptions {
+            addrs: Vec::new(),
+            unix_socket: None,
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(5),
+            write_timeout: Duration::from_secs(5),
+            nodelay: true,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            max_in_flight: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl ConnectOptions {
+    pub fn new() -> ConnectOptions {
+        ConnectOptions::default()
+    }
+
+    // Anything like "host:port", added after the ones already there.
+    pub fn addr(mut self, addr: impl ToString) -> ConnectOptions {
+        self.addrs.push(addr.to_string());
+        self
+    }
+
+    // A BITE on the same machine listening on a socket file, used instead
+    // of the addresses by Client and Connection. The TCP socket options
+    // don't apply to it, and the other clients refuse to connect.
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> ConnectOptions {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    // Covers the TCP connect and waiting for our id, for each address.
+    pub fn connect_timeout(mut self, timeout: Duration) -> ConnectOptions {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    // How long a request waits for its reply.
+    pub fn read_timeout(mut self, timeout: Duration) -> ConnectOptions {
+        self.read_timeout = timeout;
+        self
+    }
+
+    // How long a request waits for the socket to take it.
+    pub fn write_timeout(mut self, timeout: Duration) -> ConnectOptions {
+        self.write_timeout = timeout;
+        self
+    }
+
+    // On by default, small requests shouldn't wait for Nagle.
+    pub fn nodelay(mut self, nodelay: bool) -> ConnectOptions {
+        self.nodelay = nodelay;
+        self
+    }
+
+    // TCP keepalive after this long without traffic, None leaves it off.
+    pub fn keepalive(mut self, idle: Option<Duration>) -> ConnectOptions {
+        self.keepalive = idle;
+        self
+    }
+
+    pub fn send_buffer_size(mut self, size: usize) -> ConnectOptions {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    pub fn recv_buffer_size(mut self, size: usize) -> ConnectOptions {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    // Requests waiting for a reply at the same time, new ones fail with
+    // BiteError::Busy past this. Subscriptions don't count.
+    pub fn max_in_flight(mut self, max: usize) -> ConnectOptions {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    // TLS over the TCP connection, the handshake counts toward the connect
+    // timeout. Only Client and Connection speak it for now, the other
+    // clients refuse to connect.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsOptions) -> ConnectOptions {
+        self.tls = Some(tls);
+        self
+    }
+
+    // For the clients that only connect over plain TCP. Better to fail than
+    // to quietly go somewhere else, or without TLS.
+    pub(crate) fn plain_tcp(&self) -> Result<()> {
+        #[cfg(feature = "tls")]
+        if self.tls.is_some() {
+            return Err(BiteError::Tls("not supported by this client".into()));
+        }
+
+        if self.unix_socket.is_some() {
+            return Err(unsupported("unix sockets are not supported by this client"));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        let mut resolved = Vec::new();
+
+        for addr in &self.addrs {
+            resolved.extend(addr.to_socket_addrs().map_err(BiteError::Connect)?);
+        }
+
+        if resolved.is_empty() {
+            return Err(BiteError::Connect(io::Error::new(
+                ErrorKind::InvalidInput,
+                "no address to connect to",
+            )));
+        }
+
+        Ok(resolved)
+    }
+}
+
+pub(crate) fn unsupported(reason: &str) -> BiteError {
+    BiteError::Connect(io::Error::new(ErrorKind::Unsupported, reason))
+}
+
+#[cfg(test)]
+mod options_tests {
+    use std::time::Duration;
+
+    use super::ConnectOptions;
+    use crate::error::BiteError;
+
+    #[test]
+    fn builder() {
+        let options = ConnectOptions::new()
+            .addr("127.0.0.1:1984")
+            .addr("127.0.0.1:1985")
+            .read_timeout(Duration::from_secs(1))
+            .nodelay(false)
+            .max_in_flight(8);
+
+        assert_eq!(options.addrs, ["127.0.0.1:1984", "127.0.0.1:1985"]);
+        assert_eq!(options.read_timeout, Duration::from_secs(1));
+        assert!(!options.nodelay);
+        assert_eq!(options.max_in_flight, Some(8));
+
+        assert_eq!(options.resolve().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn no_address() {
+        let err = ConnectOptions::new().resolve().unwrap_err();
+        assert!(matches!(err, BiteError::Connect(_)), "{:?}", err);
+    }
+}
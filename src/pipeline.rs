@@ -0,0 +1,83 @@
+use crate::{client::Client, command::Command, error::Result, response::Response};
+
+// Commands that go out together and come back together, a round trip for
+// the whole batch instead of one per command.
+//
+//     let responses = client.pipeline().set("a", "1").inc("b").get("a").execute()?;
+//
+// The responses are in the same order as the commands. A NO from the server
+// is just another response here, not an error.
+pub struct Pipeline<'a> {
+    client: &'a mut Client,
+    commands: Vec<Command>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(client: &'a mut Client) -> Pipeline<'a> {
+        Pipeline {
+            client,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn command(mut self, command: Command) -> Pipeline<'a> {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn set(self, key: &str, value: impl Into<Vec<u8>>) -> Pipeline<'a> {
+        self.command(Command::set(key, value))
+    }
+
+    pub fn set_if_none(self, key: &str, value: impl Into<Vec<u8>>) -> Pipeline<'a> {
+        self.command(Command::set_if_none(key, value))
+    }
+
+    pub fn get(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::get(key))
+    }
+
+    pub fn delete(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::delete(key))
+    }
+
+    pub fn inc(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::inc(key))
+    }
+
+    pub fn append(self, key: &str, value: impl Into<Vec<u8>>) -> Pipeline<'a> {
+        self.command(Command::append(key, value))
+    }
+
+    pub fn keys(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::keys(key))
+    }
+
+    pub fn json(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::json(key))
+    }
+
+    pub fn json_with_key(self, key: &str) -> Pipeline<'a> {
+        self.command(Command::json_with_key(key))
+    }
+
+    pub fn call(self, key: &str, value: impl Into<Vec<u8>>) -> Pipeline<'a> {
+        self.command(Command::call(key, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn execute(self) -> Result<Vec<Response>> {
+        if self.commands.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.client.request_all(self.commands)
+    }
+}
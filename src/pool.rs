@@ -0,0 +1,409 @@
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::Client,
+    error::{BiteError, Result},
+    options::ConnectOptions,
+    reconnect::ReconnectPolicy,
+};
+
+// How many connections the pool keeps and for how long. Idle connections
+// past max_idle are closed, down to min_idle, and the ones that sat idle for
+// health_check_after are pinged before being handed out again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub max_size: usize,
+    pub min_idle: usize,
+    pub max_idle: Duration,
+    pub health_check_after: Duration,
+    pub checkout_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> PoolOptions {
+        PoolOptions {
+            max_size: 8,
+            min_idle: 1,
+            max_idle: Duration::from_secs(5 * 60),
+            health_check_after: Duration::from_secs(30),
+            checkout_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub open: usize,
+    pub idle: usize,
+    pub in_use: usize,
+    pub created: u64,
+    pub evicted: u64,
+    pub failed_checks: u64,
+    pub waited: u64,
+    pub timeouts: u64,
+}
+
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    idle: VecDeque<Idle>,
+    open: usize,
+    stats: PoolStats,
+}
+
+// Up to max_size clients, each on its own connection with its own id, shared
+// by whoever needs one. A client that breaks isn't reconnected, it's dropped
+// and a new one takes its place on the next get.
+//
+// A pooled client goes back as it is, subscribed keys included, so it's
+// better to subscribe on a client of your own.
+pub struct Pool {
+    options: ConnectOptions,
+    config: PoolOptions,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+// A client out of the pool, it goes back when dropped.
+pub struct PooledClient<'a> {
+    pool: &'a Pool,
+    client: Option<Client>,
+}
+
+impl Pool {
+    // Opens min_idle connections right away, failing if any of them does.
+    pub fn new(options: ConnectOptions, config: PoolOptions) -> Result<Pool> {
+        let pool = Pool {
+            options,
+            config,
+            state: Mutex::new(State::default()),
+            returned: Condvar::new(),
+        };
+
+        for _ in 0..pool.config.min_idle.min(pool.config.max_size) {
+            let client = pool.open()?;
+
+            let mut state = pool.lock();
+            state.open += 1;
+            state.idle.push_back(Idle {
+                client,
+                since: Instant::now(),
+            });
+        }
+
+        Ok(pool)
+    }
+
+    // An idle client if there's one, a new one if there's room, or waits
+    // for one to come back until checkout_timeout.
+    pub fn get(&self) -> Result<PooledClient<'_>> {
+        let deadline = Instant::now() + self.config.checkout_timeout;
+        let mut waited = false;
+        let mut state = self.lock();
+
+        loop {
+            self.evict(&mut state);
+
+            // The most recent first, it's the least likely to be dead.
+            if let Some(idle) = state.idle.pop_back() {
+                drop(state);
+
+                let mut client = idle.client;
+
+                if idle.since.elapsed() < self.config.health_check_after || client.ping().is_ok() {
+                    return Ok(self.lend(client));
+                }
+
+                state = self.lock();
+                state.open -= 1;
+                state.stats.failed_checks += 1;
+                continue;
+            }
+
+            if state.open < self.config.max_size {
+                state.open += 1;
+                drop(state);
+
+                return match self.open() {
+                    Ok(client) => Ok(self.lend(client)),
+
+                    Err(err) => {
+                        self.lock().open -= 1;
+                        self.returned.notify_one();
+                        Err(err)
+                    }
+                };
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                state.stats.timeouts += 1;
+                return Err(BiteError::Timeout);
+            }
+
+            if !waited {
+                waited = true;
+                state.stats.waited += 1;
+            }
+
+            state = self.returned.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let mut state = self.lock();
+        self.evict(&mut state);
+
+        PoolStats {
+            open: state.open,
+            idle: state.idle.len(),
+            in_use: state.open - state.idle.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    fn open(&self) -> Result<Client> {
+        let mut client = Client::connect_with(self.options.clone())?;
+        client.set_reconnect_policy(ReconnectPolicy::never());
+
+        self.lock().stats.created += 1;
+
+        Ok(client)
+    }
+
+    fn lend(&self, client: Client) -> PooledClient<'_> {
+        PooledClient {
+            pool: self,
+            client: Some(client),
+        }
+    }
+
+    fn give_back(&self, client: Client) {
+        let mut state = self.lock();
+
+        if client.is_closed() {
+            state.open -= 1;
+        } else {
+            state.idle.push_back(Idle {
+                client,
+                since: Instant::now(),
+            });
+        }
+
+        drop(state);
+        self.returned.notify_one();
+    }
+
+    // The oldest idle clients are at the front.
+    fn evict(&self, state: &mut State) {
+        while state.idle.len() > self.config.min_idle {
+            match state.idle.front() {
+                Some(idle) if idle.since.elapsed() >= self.config.max_idle => {
+                    state.idle.pop_front();
+                    state.open -= 1;
+                    state.stats.evicted += 1;
+                }
+
+                _ => break,
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Deref for PooledClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledClient<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledClient<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.give_back(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use std::{
+        io::Write,
+        net::{SocketAddr, TcpListener},
+        thread,
+        time::Duration,
+    };
+
+    use super::{Pool, PoolOptions};
+    use crate::{
+        error::BiteError,
+        frame::Frame,
+        options::ConnectOptions,
+        test_support::{greet, try_receive},
+    };
+
+    // Gives ids 1, 2, 3... and answers everything with an empty value. The
+    // first connection is dropped right after the greeting when asked to.
+    fn server(drop_first: bool) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for (n, socket) in listener.incoming().enumerate() {
+                let mut socket = socket.unwrap();
+                let id = n as u16 + 1;
+
+                greet(&mut socket, id);
+
+                if drop_first && n == 0 {
+                    continue;
+                }
+
+                thread::spawn(move || {
+                    while let Some(frame) = try_receive(&mut socket) {
+                        let reply = Frame::new(id, frame.id, Vec::new());
+                        socket.write_all(&reply.encode().unwrap()).unwrap();
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    fn options(addr: SocketAddr) -> ConnectOptions {
+        ConnectOptions::new().addr(addr)
+    }
+
+    #[test]
+    fn reuse() {
+        let pool = Pool::new(options(server(false)), PoolOptions::default()).unwrap();
+
+        let first = pool.get().unwrap().id();
+        let mut client = pool.get().unwrap();
+        assert_eq!(client.id(), first);
+        assert_eq!(client.get("key").unwrap(), None);
+
+        let other = pool.get().unwrap();
+        assert_ne!(other.id(), client.id());
+
+        let stats = pool.stats();
+        assert_eq!((stats.open, stats.in_use, stats.created), (2, 2, 2));
+    }
+
+    #[test]
+    fn checkout_timeout() {
+        let config = PoolOptions {
+            max_size: 1,
+            checkout_timeout: Duration::from_millis(50),
+            ..PoolOptions::default()
+        };
+
+        let pool = Pool::new(options(server(false)), config).unwrap();
+        let _client = pool.get().unwrap();
+
+        let err = pool.get().err().unwrap();
+        assert!(matches!(err, BiteError::Timeout), "{:?}", err);
+
+        let stats = pool.stats();
+        assert_eq!((stats.waited, stats.timeouts), (1, 1));
+    }
+
+    #[test]
+    fn max_idle() {
+        let config = PoolOptions {
+            min_idle: 0,
+            max_idle: Duration::from_millis(10),
+            ..PoolOptions::default()
+        };
+
+        let pool = Pool::new(options(server(false)), config).unwrap();
+        assert_eq!(pool.get().unwrap().id(), 1);
+
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(pool.stats().evicted, 1);
+        assert_eq!(pool.get().unwrap().id(), 2);
+    }
+
+    #[test]
+    fn health_check() {
+        let config = PoolOptions {
+            health_check_after: Duration::ZERO,
+            ..PoolOptions::default()
+        };
+
+        // The connection opened up front is dead by the time it's used.
+        let pool = Pool::new(options(server(true)), config).unwrap();
+        let mut client = pool.get().unwrap();
+
+        assert_eq!(client.id(), 2);
+        assert_eq!(client.get("key").unwrap(), None);
+        assert_eq!(pool.stats().failed_checks, 1);
+    }
+
+    #[test]
+    fn lost_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The first connection answers with a header that can't be right.
+        thread::spawn(move || {
+            for (n, socket) in listener.incoming().enumerate() {
+                let mut socket = socket.unwrap();
+                let id = n as u16 + 1;
+
+                greet(&mut socket, id);
+
+                thread::spawn(move || {
+                    while let Some(frame) = try_receive(&mut socket) {
+                        let reply = match n {
+                            0 => vec![0, 1, 0, 1, 0, 3],
+                            _ => Frame::new(id, frame.id, Vec::new()).encode().unwrap(),
+                        };
+
+                        socket.write_all(&reply).unwrap();
+                    }
+                });
+            }
+        });
+
+        let config = PoolOptions {
+            max_size: 1,
+            ..PoolOptions::default()
+        };
+
+        let pool = Pool::new(options(addr), config).unwrap();
+
+        let err = pool.get().unwrap().get("key").unwrap_err();
+        assert!(matches!(err, BiteError::Protocol(_)), "{:?}", err);
+
+        let mut client = pool.get().unwrap();
+        assert_eq!(client.id(), 2);
+        assert_eq!(client.get("key").unwrap(), None);
+
+        let stats = pool.stats();
+        assert_eq!((stats.open, stats.created), (1, 2));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fmt, time::Duration};
+
+use rand::{thread_rng, Rng};
+
+use crate::{command::Command, response::Response};
+
+// How the client retries after losing the server. Each attempt waits twice
+// as long as the one before, up to max_delay, and with jitter the wait is
+// randomized between half and all of that, so a fleet of clients doesn't hit
+// the server on the same tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub max_attempts: Option<u32>,
+    pub jitter: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            max_attempts: Some(10),
+            jitter: true,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // Fail right away instead of reconnecting.
+    pub fn never() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: Some(0),
+            ..ReconnectPolicy::default()
+        }
+    }
+
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if !self.jitter || delay.is_zero() {
+            return delay;
+        }
+
+        thread_rng().gen_range(delay / 2..=delay)
+    }
+
+    pub fn allows(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt <= max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconnectEvent {
+    Disconnected {
+        reason: String,
+    },
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    Reconnected {
+        id: u16,
+        attempts: u32,
+    },
+
+    // Subscriptions were sent again, anything that changed while we were
+    // away was missed.
+    Resubscribed {
+        count: usize,
+    },
+
+    // The server didn't take one of them back, no more events for it.
+    ResubscribeFailed {
+        command: Command,
+        response: Response,
+    },
+
+    GaveUp {
+        attempts: u32,
+    },
+}
+
+impl fmt::Display for ReconnectEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReconnectEvent::Disconnected { reason } => write!(f, "Disconnected ({})", reason),
+
+            ReconnectEvent::Reconnecting { attempt, delay } => {
+                write!(f, "Reconnecting in {:?}, attempt {}...", delay, attempt)
+            }
+
+            ReconnectEvent::Reconnected { id, attempts } => {
+                write!(f, "Reconnected as {} after {} attempts", id, attempts)
+            }
+
+            ReconnectEvent::Resubscribed { count } => {
+                write!(f, "Resubscribed to {} subscriptions", count)
+            }
+
+            ReconnectEvent::ResubscribeFailed { command, response } => write!(
+                f,
+                "Lost subscription {} {} ({})",
+                command.verb(),
+                command.key(),
+                response
+            ),
+
+            ReconnectEvent::GaveUp { attempts } => {
+                write!(f, "Gave up reconnecting after {} attempts", attempts)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod reconnect_tests {
+    use std::time::Duration;
+
+    use super::ReconnectPolicy;
+
+    #[test]
+    fn exponential_delay() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            max_attempts: None,
+            jitter: false,
+        };
+
+        let delays: Vec<_> = (1..=6).map(|attempt| policy.delay(attempt)).collect();
+
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter() {
+        let policy = ReconnectPolicy::default();
+
+        for attempt in 1..=20 {
+            let delay = policy.delay(attempt);
+            let max = ReconnectPolicy {
+                jitter: false,
+                ..policy.clone()
+            }
+            .delay(attempt);
+
+            assert!(delay >= max / 2 && delay <= max, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn attempts() {
+        let policy = ReconnectPolicy::default();
+
+        assert!(policy.allows(10));
+        assert!(!policy.allows(11));
+        assert!(!ReconnectPolicy::never().allows(1));
+    }
+}
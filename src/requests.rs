@@ -0,0 +1,513 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc,
+};
+
+use crate::{
+    client::Notification,
+    command::Command,
+    error::{BiteError, Result},
+    frame::{Frame, FrameDecoder, FrameError},
+    response::Response,
+};
+
+// The server echoes the message id of the request in the header of its reply,
+// that's all we need to know who's asking for what. Id 0 is left for untracked
+// messages and never handed out.
+//
+// Each command keeps the order it was sent in, ids wrap around so they
+// don't tell.
+#[derive(Debug, Default)]
+pub struct Requests {
+    last_id: u16,
+    sent: u64,
+    pending: HashMap<u16, (u64, Command)>,
+    subscriptions: HashMap<u16, (u64, Command)>,
+    limit: Option<usize>,
+    unlimited: HashSet<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Routed {
+    Reply {
+        id: u16,
+        command: Command,
+        response: Response,
+    },
+
+    // Subscription updates keep coming with the id of the subscription.
+    Event {
+        id: u16,
+        command: Command,
+        response: Response,
+    },
+
+    Unknown(Frame),
+}
+
+impl Requests {
+    pub fn new() -> Requests {
+        Requests::default()
+    }
+
+    // At most this many requests waiting for their reply at once.
+    pub fn with_limit(limit: usize) -> Requests {
+        Requests {
+            limit: Some(limit),
+            ..Requests::default()
+        }
+    }
+
+    // Reserves the next free id for this command, skipping the ones still in
+    // use after wrapping around. None when every id is taken, or the limit
+    // is reached.
+    pub fn begin(&mut self, command: Command) -> Option<u16> {
+        let limited = self.pending.len() - self.unlimited.len();
+
+        if self.limit.is_some_and(|limit| limited >= limit) {
+            return None;
+        }
+
+        self.reserve(command)
+    }
+
+    // For the client's own requests, they neither wait for the limit nor
+    // count against it.
+    pub fn begin_unlimited(&mut self, command: Command) -> Option<u16> {
+        let id = self.reserve(command)?;
+        self.unlimited.insert(id);
+
+        Some(id)
+    }
+
+    fn reserve(&mut self, command: Command) -> Option<u16> {
+        let mut id = self.last_id;
+
+        for _ in 0..u16::MAX {
+            id = id.wrapping_add(1);
+
+            if id == 0 || self.is_busy(id) {
+                continue;
+            }
+
+            self.last_id = id;
+            self.sent += 1;
+            self.pending.insert(id, (self.sent, command));
+
+            return Some(id);
+        }
+
+        None
+    }
+
+    pub fn route(&mut self, frame: Frame) -> Routed {
+        if let Some((sent, command)) = self.pending.remove(&frame.id) {
+            self.unlimited.remove(&frame.id);
+            let response = Response::parse(&command, &frame.payload);
+
+            if command.is_subscription() && response == Response::Ok {
+                self.subscriptions.insert(frame.id, (sent, command.clone()));
+            }
+
+            return Routed::Reply {
+                id: frame.id,
+                command,
+                response,
+            };
+        }
+
+        if let Some((_, command)) = self.subscriptions.get(&frame.id) {
+            return Routed::Event {
+                id: frame.id,
+                command: command.clone(),
+                response: Response::parse_event(command, &frame.payload),
+            };
+        }
+
+        Routed::Unknown(frame)
+    }
+
+    // Turns every active subscription back into a pending request with the
+    // same id, ready to be sent again on a new connection.
+    pub fn resubscribe(&mut self) -> Vec<u16> {
+        let ids: Vec<u16> = self.subscriptions.keys().copied().collect();
+        self.pending.extend(self.subscriptions.drain());
+
+        ids
+    }
+
+    pub fn cancel(&mut self, id: u16) -> Option<Command> {
+        self.unlimited.remove(&id);
+        self.pending.remove(&id).map(|(_, command)| command)
+    }
+
+    // Forgets an active subscription, its updates become unknown.
+    pub fn unsubscribe(&mut self, id: u16) -> Option<Command> {
+        self.subscriptions.remove(&id).map(|(_, command)| command)
+    }
+
+    pub fn is_pending(&self, id: u16) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    // In the order they were sent.
+    pub fn pending(&self) -> impl Iterator<Item = (u16, &Command)> {
+        let mut pending: Vec<_> = self.pending.iter().collect();
+        pending.sort_unstable_by_key(|(_, (sent, _))| *sent);
+
+        pending.into_iter().map(|(&id, (_, command))| (id, command))
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn subscriptions(&self) -> impl Iterator<Item = (u16, &Command)> {
+        self.subscriptions
+            .iter()
+            .map(|(&id, (_, command))| (id, command))
+    }
+
+    fn is_busy(&self, id: u16) -> bool {
+        self.pending.contains_key(&id) || self.subscriptions.contains_key(&id)
+    }
+}
+
+// Where a reply or an event goes, false once nobody listens anymore.
+pub(crate) trait Deliver<T> {
+    fn deliver(&self, value: T) -> bool;
+}
+
+impl<T> Deliver<T> for mpsc::Sender<T> {
+    fn deliver(&self, value: T) -> bool {
+        self.send(value).is_ok()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T> Deliver<T> for tokio::sync::mpsc::UnboundedSender<T> {
+    fn deliver(&self, value: T) -> bool {
+        self.send(value).is_ok()
+    }
+}
+
+// The requests of a client whose callers wait each on their own channel,
+// shared by them and the reader that routes every frame. Closing drops the
+// channels, that wakes up every caller and ends every subscription.
+pub(crate) struct Waiters<R, N> {
+    pub requests: Requests,
+    waiting: HashMap<u16, R>,
+    subscribers: HashMap<u16, N>,
+    mismatches: usize,
+    closed: bool,
+}
+
+impl<R: Deliver<Response>, N: Deliver<Notification>> Waiters<R, N> {
+    pub fn new(requests: Requests) -> Waiters<R, N> {
+        Waiters {
+            requests,
+            waiting: HashMap::new(),
+            subscribers: HashMap::new(),
+            mismatches: 0,
+            closed: false,
+        }
+    }
+
+    // Reserves an id for the command, its reply goes to reply and the
+    // updates of a subscription to events.
+    pub fn begin(&mut self, command: Command, reply: R, events: Option<N>) -> Result<u16> {
+        if self.closed {
+            return Err(BiteError::Disconnected);
+        }
+
+        let id = self.requests.begin(command).ok_or(BiteError::Busy)?;
+        self.waiting.insert(id, reply);
+
+        if let Some(events) = events {
+            self.subscribers.insert(id, events);
+        }
+
+        Ok(id)
+    }
+
+    // A late reply will be unknown and ignored.
+    pub fn cancel(&mut self, id: u16) {
+        self.requests.cancel(id);
+        self.waiting.remove(&id);
+        self.subscribers.remove(&id);
+    }
+
+    // Nobody listens to the subscription anymore.
+    pub fn unsubscribe(&mut self, id: u16) {
+        self.requests.unsubscribe(id);
+        self.subscribers.remove(&id);
+    }
+
+    // Routes every complete frame in the decoder. False when we lost track
+    // of the stream, then it's closed.
+    pub fn dispatch_all(&mut self, decoder: &mut FrameDecoder) -> bool {
+        loop {
+            match decoder.next_frame() {
+                Ok(Some(frame)) => self.dispatch(frame),
+                Ok(None) => return true,
+
+                // Meant for another client, it was skipped.
+                Err(FrameError::WrongSender { .. }) => self.mismatches += 1,
+
+                Err(_) => {
+                    self.close();
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, frame: Frame) {
+        match self.requests.route(frame) {
+            Routed::Reply { id, response, .. } => {
+                // A subscription that wasn't accepted sends nothing else.
+                if response != Response::Ok {
+                    self.subscribers.remove(&id);
+                }
+
+                // Whoever was waiting may have given up already.
+                if let Some(reply) = self.waiting.remove(&id) {
+                    reply.deliver(response);
+                }
+            }
+
+            Routed::Event {
+                id,
+                command,
+                response,
+            } => {
+                if let Some(subscriber) = self.subscribers.get(&id) {
+                    if !subscriber.deliver(Notification { command, response }) {
+                        self.unsubscribe(id);
+                    }
+                }
+            }
+
+            Routed::Unknown(_) => {}
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.waiting.clear();
+        self.subscribers.clear();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // Frames that came addressed to another client id.
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+}
+
+#[cfg(test)]
+mod requests_tests {
+    use std::sync::mpsc;
+
+    use super::{Requests, Routed, Waiters};
+    use crate::{
+        client::Notification,
+        command::Command,
+        error::BiteError,
+        frame::{Frame, FrameDecoder},
+        response::Response,
+    };
+
+    #[test]
+    fn out_of_order_replies() {
+        let mut requests = Requests::new();
+
+        let set = requests.begin(Command::set("set", "SET")).unwrap();
+        let get = requests.begin(Command::get("set")).unwrap();
+        let inc = requests.begin(Command::inc("inc")).unwrap();
+
+        assert_eq!((set, get, inc), (1, 2, 3));
+        assert_eq!(requests.in_flight(), 3);
+
+        assert_eq!(
+            requests.route(Frame::new(1, inc, vec![0, 0, 0, 0, 0, 0, 0, 1])),
+            Routed::Reply {
+                id: inc,
+                command: Command::inc("inc"),
+                response: Response::Counter(1)
+            }
+        );
+        assert_eq!(
+            requests.route(Frame::new(1, set, b"OK".to_vec())),
+            Routed::Reply {
+                id: set,
+                command: Command::set("set", "SET"),
+                response: Response::Ok
+            }
+        );
+        assert_eq!(
+            requests.route(Frame::new(1, get, b"SET".to_vec())),
+            Routed::Reply {
+                id: get,
+                command: Command::get("set"),
+                response: Response::Value(b"SET".to_vec())
+            }
+        );
+
+        assert_eq!(requests.in_flight(), 0);
+    }
+
+    #[test]
+    fn unknown_id() {
+        let mut requests = Requests::new();
+        let frame = Frame::new(1, 7, b"OK".to_vec());
+
+        assert_eq!(requests.route(frame.clone()), Routed::Unknown(frame));
+    }
+
+    #[test]
+    fn wraparound_skips_zero_and_busy_ids() {
+        let mut requests = Requests::new();
+        requests.last_id = u16::MAX - 1;
+
+        let busy = requests.begin(Command::get("busy")).unwrap();
+        assert_eq!(busy, u16::MAX);
+
+        assert_eq!(requests.begin(Command::get("a")), Some(1));
+
+        requests.last_id = u16::MAX - 1;
+        assert_eq!(requests.begin(Command::get("b")), Some(2));
+    }
+
+    #[test]
+    fn all_ids_busy() {
+        let mut requests = Requests::new();
+
+        for _ in 0..u16::MAX {
+            assert!(requests.begin(Command::get("key")).is_some());
+        }
+
+        assert_eq!(requests.begin(Command::get("key")), None);
+
+        requests.cancel(42);
+        assert_eq!(requests.begin(Command::get("key")), Some(42));
+    }
+
+    #[test]
+    fn subscription_events() {
+        let mut requests = Requests::new();
+
+        let id = requests.begin(Command::subscribe_get("subs")).unwrap();
+        requests.route(Frame::new(1, id, b"OK".to_vec()));
+
+        assert_eq!(
+            requests.route(Frame::new(1, id, b"SET".to_vec())),
+            Routed::Event {
+                id,
+                command: Command::subscribe_get("subs"),
+                response: Response::Value(b"SET".to_vec())
+            }
+        );
+        assert_eq!(requests.subscriptions().count(), 1);
+        assert_ne!(requests.begin(Command::get("subs")), Some(id));
+    }
+
+    #[test]
+    fn resubscribe() {
+        let mut requests = Requests::new();
+
+        let id = requests.begin(Command::subscribe_keys("subs")).unwrap();
+        requests.route(Frame::new(1, id, b"OK".to_vec()));
+
+        assert_eq!(requests.resubscribe(), vec![id]);
+        assert_eq!(requests.subscriptions().count(), 0);
+        assert!(requests.is_pending(id));
+
+        requests.route(Frame::new(2, id, b"OK".to_vec()));
+        assert_eq!(requests.subscriptions().count(), 1);
+    }
+
+    #[test]
+    fn limit() {
+        let mut requests = Requests::with_limit(2);
+
+        let first = requests.begin(Command::get("a")).unwrap();
+        requests.begin(Command::get("b")).unwrap();
+        assert_eq!(requests.begin(Command::get("c")), None);
+
+        requests.route(Frame::new(1, first, b"A".to_vec()));
+        assert!(requests.begin(Command::get("c")).is_some());
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut requests = Requests::with_limit(1);
+
+        let probe = requests.begin_unlimited(Command::get("probe")).unwrap();
+        let first = requests.begin(Command::get("a")).unwrap();
+        assert_eq!(requests.begin(Command::get("b")), None);
+
+        requests.route(Frame::new(1, first, b"A".to_vec()));
+        requests.begin(Command::get("b")).unwrap();
+        requests.cancel(probe);
+        assert_eq!(requests.begin(Command::get("c")), None);
+    }
+
+    #[test]
+    fn waiters() {
+        let mut waiters = Waiters::new(Requests::new());
+        let mut decoder = FrameDecoder::new();
+
+        let (reply, replies) = mpsc::channel();
+        let (events, notifications) = mpsc::channel::<Notification>();
+        let command = Command::subscribe_get("subs");
+        let id = waiters.begin(command, reply.clone(), Some(events)).unwrap();
+
+        let get = waiters.begin(Command::get("get"), reply, None).unwrap();
+        waiters.cancel(get);
+
+        for (id, payload) in [(id, &b"OK"[..]), (get, b"LATE"), (id, b"SET")] {
+            decoder.push(&Frame::new(1, id, payload.to_vec()).encode().unwrap());
+        }
+
+        assert!(waiters.dispatch_all(&mut decoder));
+        assert_eq!(replies.try_iter().collect::<Vec<_>>(), [Response::Ok]);
+        assert_eq!(
+            notifications.try_recv().unwrap().response,
+            Response::Value(b"SET".to_vec())
+        );
+
+        // Lost track of the stream.
+        decoder.push(&[0, 1, 0, 1, 0, 3]);
+        assert!(!waiters.dispatch_all(&mut decoder));
+        assert!(notifications.recv().is_err());
+
+        let (reply, _) = mpsc::channel();
+        let err = waiters.begin(Command::get("get"), reply, None).unwrap_err();
+        assert!(matches!(err, BiteError::Disconnected), "{:?}", err);
+    }
+
+    #[test]
+    fn dropped_subscriber() {
+        let mut waiters = Waiters::new(Requests::new());
+        let mut decoder = FrameDecoder::new();
+
+        let (reply, _replies) = mpsc::channel();
+        let (events, notifications) = mpsc::channel::<Notification>();
+        let command = Command::subscribe_get("subs");
+        let id = waiters.begin(command, reply, Some(events)).unwrap();
+
+        decoder.push(&Frame::new(1, id, b"OK".to_vec()).encode().unwrap());
+        assert!(waiters.dispatch_all(&mut decoder));
+        assert_eq!(waiters.requests.subscriptions().count(), 1);
+
+        // The next update finds nobody listening.
+        drop(notifications);
+        decoder.push(&Frame::new(1, id, b"SET".to_vec()).encode().unwrap());
+        assert!(waiters.dispatch_all(&mut decoder));
+        assert_eq!(waiters.requests.subscriptions().count(), 0);
+    }
+}
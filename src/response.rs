@@ -0,0 +1,218 @@
+use std::fmt;
+
+use crate::command::Command;
+
+// What a payload means depends on the instruction that caused it, BITE
+// doesn't tag its replies. An empty payload is how the server says there is
+// nothing under that key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Ok,
+    No,
+    Missing,
+    Value(Vec<u8>),
+    Counter(u64),
+    Keys(Vec<(String, Vec<u8>)>),
+    Json(String),
+}
+
+impl Response {
+    pub fn parse(command: &Command, payload: &[u8]) -> Response {
+        match command {
+            Command::Set { .. }
+            | Command::SetIfNone { .. }
+            | Command::Delete { .. }
+            | Command::Append { .. }
+            | Command::Call { .. }
+            | Command::SubscribeGet { .. }
+            | Command::SubscribeKeys { .. }
+            | Command::SubscribeJson { .. } => status(payload),
+
+            Command::Get { .. } => value(payload),
+            Command::Inc { .. } => counter(payload),
+            Command::Keys { .. } => keys(payload),
+            Command::Json { .. } | Command::JsonWithKey { .. } => json(payload),
+        }
+    }
+
+    // Subscriptions answer OK once, then every change on the key sends what
+    // the subscribed instruction would reply.
+    pub fn parse_event(command: &Command, payload: &[u8]) -> Response {
+        match command {
+            Command::SubscribeGet { .. } => value(payload),
+            Command::SubscribeKeys { .. } => keys(payload),
+            Command::SubscribeJson { .. } => json(payload),
+
+            _ => Response::parse(command, payload),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Ok => write!(f, "OK"),
+            Response::No => write!(f, "NO"),
+            Response::Missing => write!(f, "(missing)"),
+            Response::Value(value) => write!(f, "{}", String::from_utf8_lossy(value)),
+            Response::Counter(count) => write!(f, "{}", count),
+
+            Response::Keys(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+
+                    write!(f, "{} {}", key, String::from_utf8_lossy(value))?;
+                }
+
+                Ok(())
+            }
+
+            Response::Json(json) => write!(f, "{}", json),
+        }
+    }
+}
+
+fn status(payload: &[u8]) -> Response {
+    match payload {
+        b"OK" => Response::Ok,
+        b"NO" => Response::No,
+        _ => value(payload),
+    }
+}
+
+fn value(payload: &[u8]) -> Response {
+    if payload.is_empty() {
+        return Response::Missing;
+    }
+
+    Response::Value(payload.to_vec())
+}
+
+// Counters are 8 bytes, big-endian.
+fn counter(payload: &[u8]) -> Response {
+    match <[u8; 8]>::try_from(payload) {
+        Ok(bytes) => Response::Counter(u64::from_be_bytes(bytes)),
+        Err(_) => status(payload),
+    }
+}
+
+// Pairs of "key value" separated by NUL bytes.
+fn keys(payload: &[u8]) -> Response {
+    if payload.is_empty() {
+        return Response::Missing;
+    }
+
+    let pairs = payload
+        .split(|&byte| byte == 0)
+        .map(|pair| match pair.iter().position(|&byte| byte == b' ') {
+            Some(space) => (
+                String::from_utf8_lossy(&pair[..space]).into_owned(),
+                pair[space + 1..].to_vec(),
+            ),
+
+            None => (String::from_utf8_lossy(pair).into_owned(), Vec::new()),
+        })
+        .collect();
+
+    Response::Keys(pairs)
+}
+
+fn json(payload: &[u8]) -> Response {
+    if payload.is_empty() {
+        return Response::Missing;
+    }
+
+    Response::Json(String::from_utf8_lossy(payload).into_owned())
+}
+
+#[cfg(test)]
+mod response_tests {
+    use super::Response;
+    use crate::command::Command;
+
+    #[test]
+    fn status() {
+        let set = Command::set("set", "SET");
+
+        assert_eq!(Response::parse(&set, b"OK"), Response::Ok);
+        assert_eq!(Response::parse(&set, b"NO"), Response::No);
+    }
+
+    #[test]
+    fn get() {
+        let get = Command::get("set");
+
+        assert_eq!(
+            Response::parse(&get, b"SET"),
+            Response::Value(b"SET".to_vec())
+        );
+        assert_eq!(
+            Response::parse(&get, b"OK"),
+            Response::Value(b"OK".to_vec())
+        );
+        assert_eq!(Response::parse(&get, b""), Response::Missing);
+    }
+
+    #[test]
+    fn inc() {
+        let inc = Command::inc("inc");
+
+        assert_eq!(
+            Response::parse(&inc, &[0, 0, 0, 0, 0, 0, 0, 2]),
+            Response::Counter(2)
+        );
+        assert_eq!(
+            Response::parse(&inc, &[0, 0, 0, 0, 0, 0, 1, 0]),
+            Response::Counter(256)
+        );
+        assert_eq!(Response::parse(&inc, b"NO"), Response::No);
+    }
+
+    #[test]
+    fn keys() {
+        let keys = Command::keys("kv");
+
+        assert_eq!(
+            Response::parse(&keys, b"1 1\x002 1.2\x003 1.2.3"),
+            Response::Keys(vec![
+                ("1".to_string(), b"1".to_vec()),
+                ("2".to_string(), b"1.2".to_vec()),
+                ("3".to_string(), b"1.2.3".to_vec()),
+            ])
+        );
+        assert_eq!(Response::parse(&keys, b""), Response::Missing);
+    }
+
+    #[test]
+    fn json() {
+        let json = Command::json("j");
+
+        assert_eq!(
+            Response::parse(&json, br#"{"1":[49],"2":[50]}"#),
+            Response::Json(r#"{"1":[49],"2":[50]}"#.to_string())
+        );
+        assert_eq!(Response::parse(&json, b""), Response::Missing);
+    }
+
+    #[test]
+    fn subscription_events() {
+        assert_eq!(
+            Response::parse(&Command::subscribe_get("subs"), b"OK"),
+            Response::Ok
+        );
+        assert_eq!(
+            Response::parse_event(&Command::subscribe_get("subs"), b"OK"),
+            Response::Value(b"OK".to_vec())
+        );
+        assert_eq!(
+            Response::parse_event(&Command::subscribe_keys("subs"), b"subs SET"),
+            Response::Keys(vec![("subs".to_string(), b"SET".to_vec())])
+        );
+        assert_eq!(
+            Response::parse_event(&Command::subscribe_json("subs"), br#"{"subs":"SET"}"#),
+            Response::Json(r#"{"subs":"SET"}"#.to_string())
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use std::{
+    io::{
+        self,
+        ErrorKind::{Interrupted, TimedOut, WouldBlock, WriteZero},
+        IoSlice, Write,
+    },
+    net::{Shutdown, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    client::{expect_json, expect_ok, unexpected, Notification},
+    command::Command,
+    connection::{connect_any, greeted},
+    error::{BiteError, Result},
+    frame::{Frame, FrameDecoder},
+    options::ConnectOptions,
+    requests::{Requests, Waiters},
+    response::Response,
+};
+
+// Everything the reader thread and the callers share.
+type Shared = Waiters<Sender<Response>, Sender<Notification>>;
+
+// The same commands as Client, but every call takes &self, so one client can
+// be shared by many threads behind an Arc. Each call writes its frame and
+// waits on its own channel, a reader thread routes every reply to the caller
+// with the same message id.
+//
+// There's no reconnection here, when the connection drops every call fails
+// with BiteError::Disconnected and the subscriptions end. It only speaks
+// plain TCP, options with TLS or a socket file are refused.
+pub struct SharedClient {
+    id: u16,
+    options: ConnectOptions,
+    shared: Arc<Mutex<Shared>>,
+    writer: Mutex<TcpStream>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl SharedClient {
+    // Default options on this address, resolved again on every connect.
+    pub fn connect(addr: impl ToString) -> Result<SharedClient> {
+        SharedClient::connect_with(ConnectOptions::new().addr(addr))
+    }
+
+    pub fn connect_with(options: ConnectOptions) -> Result<SharedClient> {
+        options.plain_tcp()?;
+
+        let mut socket = connect_any(&options)?;
+        let mut decoder = FrameDecoder::new();
+
+        socket.set_read_timeout(Some(options.connect_timeout))?;
+        let id = handshake(&mut socket, &mut decoder)?;
+
+        socket.set_read_timeout(None)?;
+        socket.set_write_timeout(Some(options.write_timeout))?;
+
+        let shared = Arc::new(Mutex::new(Shared::new(
+            options
+                .max_in_flight
+                .map_or_else(Requests::new, Requests::with_limit),
+        )));
+
+        let reader = {
+            let socket = socket.try_clone()?;
+            let shared = shared.clone();
+
+            thread::Builder::new()
+                .name("bitenc-reader".into())
+                .spawn(move || read_frames(socket, decoder, shared))?
+        };
+
+        Ok(SharedClient {
+            id,
+            options,
+            shared,
+            writer: Mutex::new(socket),
+            reader: Some(reader),
+        })
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().is_closed()
+    }
+
+    // Frames that came addressed to another client id.
+    pub fn mismatched_frames(&self) -> usize {
+        self.shared.lock().unwrap().mismatches()
+    }
+
+    pub fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set(key, value))?)
+    }
+
+    pub fn set_if_none(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::set_if_none(key, value))?)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.request(Command::get(key))? {
+            Response::Value(value) => Ok(Some(value)),
+            Response::Missing => Ok(None),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        expect_ok(self.request(Command::delete(key))?)
+    }
+
+    pub fn inc(&self, key: &str) -> Result<u64> {
+        match self.request(Command::inc(key))? {
+            Response::Counter(count) => Ok(count),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::append(key, value))?)
+    }
+
+    pub fn keys(&self, key: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        match self.request(Command::keys(key))? {
+            Response::Keys(pairs) => Ok(pairs),
+            Response::Missing => Ok(Vec::new()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn json(&self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json(key))?)
+    }
+
+    pub fn json_with_key(&self, key: &str) -> Result<Option<String>> {
+        expect_json(self.request(Command::json_with_key(key))?)
+    }
+
+    pub fn call(&self, key: &str, value: &[u8]) -> Result<()> {
+        expect_ok(self.request(Command::call(key, value))?)
+    }
+
+    // The updates come through the receiver, it disconnects with the
+    // connection. Once the receiver is dropped the subscription is
+    // forgotten on the next update.
+    pub fn subscribe_get(&self, key: &str) -> Result<Receiver<Notification>> {
+        self.subscribe(Command::subscribe_get(key))
+    }
+
+    pub fn subscribe_keys(&self, key: &str) -> Result<Receiver<Notification>> {
+        self.subscribe(Command::subscribe_keys(key))
+    }
+
+    pub fn subscribe_json(&self, key: &str) -> Result<Receiver<Notification>> {
+        self.subscribe(Command::subscribe_json(key))
+    }
+
+    // Sends any command and blocks until its own reply arrives.
+    pub fn request(&self, command: Command) -> Result<Response> {
+        let (id, reply) = self.send(command, None)?;
+        self.wait_reply(id, reply)
+    }
+
+    fn subscribe(&self, command: Command) -> Result<Receiver<Notification>> {
+        // Registered before sending, the first update may come right after
+        // the OK.
+        let (sender, events) = mpsc::channel();
+
+        let (id, reply) = self.send(command, Some(sender))?;
+        expect_ok(self.wait_reply(id, reply)?)?;
+
+        Ok(events)
+    }
+
+    fn send(
+        &self,
+        command: Command,
+        events: Option<Sender<Notification>>,
+    ) -> Result<(u16, Receiver<Response>)> {
+        let payload = command.payload()?;
+
+        let (sender, reply) = mpsc::channel();
+
+        let id = self.shared.lock().unwrap().begin(command, sender, events)?;
+
+        let frame = Frame::new(self.id, id, payload);
+        let result = write_frame(&mut self.writer.lock().unwrap(), &frame);
+
+        // Half a frame may be on the wire, nothing after it would make sense
+        // to the server.
+        if let Err(err) = result {
+            self.close();
+
+            return Err(match err.kind() {
+                WouldBlock | TimedOut => BiteError::Timeout,
+                _ => err.into(),
+            });
+        }
+
+        Ok((id, reply))
+    }
+
+    fn wait_reply(&self, id: u16, reply: Receiver<Response>) -> Result<Response> {
+        match reply.recv_timeout(self.options.read_timeout) {
+            Ok(response) => Ok(response),
+
+            Err(RecvTimeoutError::Timeout) => {
+                self.shared.lock().unwrap().cancel(id);
+                Err(BiteError::Timeout)
+            }
+
+            // The connection closed and took the sender with it.
+            Err(RecvTimeoutError::Disconnected) => Err(BiteError::Disconnected),
+        }
+    }
+
+    // The reader thread wakes up to the shutdown and leaves.
+    fn close(&self) {
+        self.shared.lock().unwrap().close();
+
+        if let Ok(socket) = self.writer.lock() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for SharedClient {
+    fn drop(&mut self) {
+        self.close();
+
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn handshake(socket: &mut TcpStream, decoder: &mut FrameDecoder) -> Result<u16> {
+    loop {
+        if let Some(id) = greeted(decoder)? {
+            return Ok(id);
+        }
+
+        match decoder.read_from(socket) {
+            Ok(0) => return Err(BiteError::Disconnected),
+            Ok(_) => {}
+
+            // The read timeout, it's WouldBlock on some systems and
+            // TimedOut on others.
+            Err(ref err) if matches!(err.kind(), WouldBlock | TimedOut) => {
+                return Err(BiteError::Handshake("no id from the server".into()))
+            }
+
+            // Got interrupted, we'll try again.
+            Err(ref err) if err.kind() == Interrupted => {}
+
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn read_frames(mut socket: TcpStream, mut decoder: FrameDecoder, shared: Arc<Mutex<Shared>>) {
+    loop {
+        // We lost track of the stream.
+        if !shared.lock().unwrap().dispatch_all(&mut decoder) {
+            let _ = socket.shutdown(Shutdown::Both);
+            return;
+        }
+
+        match decoder.read_from(&mut socket) {
+            Ok(0) => break,
+            Ok(_) => {}
+
+            // Got interrupted, we'll try again.
+            Err(ref err) if err.kind() == Interrupted => {}
+
+            Err(_) => break,
+        }
+    }
+
+    shared.lock().unwrap().close();
+}
+
+// The header and the payload together, without copying the payload.
+fn write_frame(socket: &mut TcpStream, frame: &Frame) -> io::Result<()> {
+    let header = frame.header()?;
+
+    let mut slices = [IoSlice::new(&header), IoSlice::new(&frame.payload)];
+    let count = if frame.payload.is_empty() { 1 } else { 2 };
+    let mut slices = &mut slices[..count];
+
+    while !slices.is_empty() {
+        match socket.write_vectored(slices) {
+            Ok(0) => return Err(WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+
+            // Got interrupted, we'll try again.
+            Err(ref err) if err.kind() == Interrupted => {}
+
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod shared_client_tests {
+    use std::{
+        io::{ErrorKind, Write},
+        net::TcpListener,
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use super::SharedClient;
+    use crate::{
+        command::Command,
+        error::BiteError,
+        frame::Frame,
+        options::ConnectOptions,
+        response::Response,
+        test_support::{greet, receive},
+    };
+
+    #[cfg(feature = "tls")]
+    use crate::tls::TlsOptions;
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<SharedClient>();
+    }
+
+    #[test]
+    fn wrong_sender() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            // Someone else's reply comes first.
+            let frame = receive(&mut socket);
+            let mut data = Frame::new(2, frame.id, b"OTHER".to_vec()).encode().unwrap();
+            data.extend_from_slice(&Frame::new(1, frame.id, b"MINE".to_vec()).encode().unwrap());
+            socket.write_all(&data).unwrap();
+        });
+
+        let client = SharedClient::connect(addr).unwrap();
+        assert_eq!(client.get("key").unwrap(), Some(b"MINE".to_vec()));
+        assert_eq!(client.mismatched_frames(), 1);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn many_threads() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            // Replies only after every thread asked, last one first, each
+            // with the key it asked for.
+            let frames: Vec<Frame> = (0..8).map(|_| receive(&mut socket)).collect();
+
+            for frame in frames.iter().rev() {
+                let key = &frame.payload[2..];
+                let reply = Frame::new(1, frame.id, key.to_vec());
+                socket.write_all(&reply.encode().unwrap()).unwrap();
+            }
+        });
+
+        let client = Arc::new(SharedClient::connect(addr).unwrap());
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    let key = format!("key{}", i);
+                    assert_eq!(client.get(&key).unwrap(), Some(key.into_bytes()));
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn subscription() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            greet(&mut socket, 1);
+
+            let frame = receive(&mut socket);
+            let mut data = Frame::new(1, frame.id, b"OK".to_vec()).encode().unwrap();
+            data.extend_from_slice(&Frame::new(1, frame.id, b"ONE".to_vec()).encode().unwrap());
+
+            socket.write_all(&data).unwrap();
+        });
+
+        let client = SharedClient::connect(addr).unwrap();
+        let events = client.subscribe_get("subs").unwrap();
+
+        let notification = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(notification.command, Command::subscribe_get("subs"));
+        assert_eq!(notification.response, Response::Value(b"ONE".to_vec()));
+
+        server.join().unwrap();
+
+        // The server hung up.
+        assert!(events.recv_timeout(Duration::from_secs(1)).is_err());
+        assert!(client.get("subs").unwrap_err().is_disconnect());
+    }
+
+    #[test]
+    fn rejects_unix_socket() {
+        let options = ConnectOptions::new()
+            .addr("127.0.0.1:1984")
+            .unix_socket("/tmp/bite.sock");
+
+        match SharedClient::connect_with(options).err().unwrap() {
+            BiteError::Connect(err) => assert_eq!(err.kind(), ErrorKind::Unsupported),
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn rejects_tls() {
+        let options = ConnectOptions::new()
+            .addr("127.0.0.1:1984")
+            .tls(TlsOptions::new());
+
+        let err = SharedClient::connect_with(options).err().unwrap();
+        assert!(matches!(err, BiteError::Tls(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,27 @@
+// What the fake servers in the tests need to speak BITE.
+use std::io::{Read, Write};
+
+use crate::frame::{Frame, HEADER_SIZE};
+
+// The id frame every new client gets first.
+pub fn greet(socket: &mut impl Write, id: u16) {
+    let frame = Frame::new(id, 0, Vec::new());
+    socket.write_all(&frame.encode().unwrap()).unwrap();
+}
+
+pub fn receive(socket: &mut impl Read) -> Frame {
+    try_receive(socket).expect("the client hung up")
+}
+
+// None once the client hangs up.
+pub fn try_receive(socket: &mut impl Read) -> Option<Frame> {
+    let mut header = [0; HEADER_SIZE];
+    socket.read_exact(&mut header).ok()?;
+
+    let size = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut data = header.to_vec();
+    data.resize(size, 0);
+    socket.read_exact(&mut data[HEADER_SIZE..]).ok()?;
+
+    Frame::decode(&data).ok()
+}
@@ -0,0 +1,415 @@
+use std::{
+    io::{self, ErrorKind, IoSlice, Read, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, ServerName},
+    ClientConfig, ClientConnection, RootCertStore,
+};
+
+pub use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+use crate::{
+    error::{BiteError, Result},
+    options::ConnectOptions,
+    transport::{Ready, TcpTransport, Transport},
+};
+
+// How to talk TLS to a BITE behind a terminator. Without a CA of our own the
+// usual public roots are trusted, with one only the ones given are.
+//
+//     let tls = TlsOptions::new()
+//         .ca_file("ca.pem")?
+//         .client_cert_files("client.pem", "client.key")?;
+//
+//     let client = Client::connect_with(ConnectOptions::new().addr("bite.local:1984").tls(tls))?;
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub(crate) server_name: Option<String>,
+    pub(crate) ca_certs: Vec<CertificateDer<'static>>,
+    pub(crate) client_cert: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+// PrivateKeyDer isn't Clone, so it doesn't get copied by accident.
+impl Clone for TlsOptions {
+    fn clone(&self) -> TlsOptions {
+        TlsOptions {
+            server_name: self.server_name.clone(),
+            ca_certs: self.ca_certs.clone(),
+            client_cert: self
+                .client_cert
+                .as_ref()
+                .map(|(chain, key)| (chain.clone(), key.clone_key())),
+        }
+    }
+}
+
+impl TlsOptions {
+    pub fn new() -> TlsOptions {
+        TlsOptions::default()
+    }
+
+    // The name the certificate is checked against, the host of the first
+    // address by default.
+    pub fn server_name(mut self, name: impl ToString) -> TlsOptions {
+        self.server_name = Some(name.to_string());
+        self
+    }
+
+    pub fn ca_cert(mut self, cert: CertificateDer<'static>) -> TlsOptions {
+        self.ca_certs.push(cert);
+        self
+    }
+
+    // Every certificate in a PEM file.
+    pub fn ca_file(mut self, path: impl AsRef<Path>) -> Result<TlsOptions> {
+        for cert in CertificateDer::pem_file_iter(path).map_err(invalid)? {
+            self.ca_certs.push(cert.map_err(invalid)?);
+        }
+
+        Ok(self)
+    }
+
+    // For servers that ask who we are.
+    pub fn client_cert(
+        mut self,
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> TlsOptions {
+        self.client_cert = Some((chain, key));
+        self
+    }
+
+    // The chain and the key as PEM files, they can be the same file.
+    pub fn client_cert_files(
+        self,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Result<TlsOptions> {
+        let chain = CertificateDer::pem_file_iter(cert)
+            .map_err(invalid)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+
+        let key = PrivateKeyDer::from_pem_file(key).map_err(invalid)?;
+
+        Ok(self.client_cert(chain, key))
+    }
+
+    pub(crate) fn config(&self) -> Result<Arc<ClientConfig>> {
+        let mut roots = RootCertStore::empty();
+
+        if self.ca_certs.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+
+        for cert in &self.ca_certs {
+            roots.add(cert.clone()).map_err(invalid)?;
+        }
+
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(invalid)?
+            .with_root_certificates(roots);
+
+        let config = match &self.client_cert {
+            Some((chain, key)) => builder
+                .with_client_auth_cert(chain.clone(), key.clone_key())
+                .map_err(invalid)?,
+
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(Arc::new(config))
+    }
+
+    pub(crate) fn name(&self, options: &ConnectOptions) -> Result<ServerName<'static>> {
+        let name = match (&self.server_name, options.addrs.first()) {
+            (Some(name), _) => name.clone(),
+
+            // Whatever is before the port, without the brackets of an IPv6.
+            (None, Some(addr)) => addr
+                .rsplit_once(':')
+                .map_or(addr.as_str(), |(host, _)| host)
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+
+            (None, None) => return Err(BiteError::Tls("no server name to check".to_string())),
+        };
+
+        ServerName::try_from(name).map_err(invalid)
+    }
+}
+
+fn invalid(err: impl ToString) -> BiteError {
+    BiteError::Tls(err.to_string())
+}
+
+// A TCP transport with rustls in between. The handshake happens as the first
+// bytes are read and written, so the greeting waits for it.
+pub struct TlsTransport {
+    tls: ClientConnection,
+    socket: TcpTransport,
+}
+
+impl TlsTransport {
+    pub fn new(
+        socket: TcpTransport,
+        config: Arc<ClientConfig>,
+        name: ServerName<'static>,
+    ) -> Result<TlsTransport> {
+        let tls = ClientConnection::new(config, name).map_err(invalid)?;
+
+        Ok(TlsTransport { tls, socket })
+    }
+
+    // Sends what rustls has for the socket, until it's all out or the socket
+    // is full.
+    fn send_tls(&mut self) -> io::Result<()> {
+        while self.tls.wants_write() {
+            match self.tls.write_tls(&mut self.socket) {
+                Ok(_) => {}
+
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) -> io::Result<usize> {
+        match self.tls.process_new_packets() {
+            Ok(state) => Ok(state.plaintext_bytes_to_read()),
+
+            Err(err) => {
+                // Let the server know why before giving up.
+                let _ = self.send_tls();
+                Err(io::Error::new(ErrorKind::InvalidData, err))
+            }
+        }
+    }
+}
+
+impl Read for TlsTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            self.send_tls()?;
+
+            match self.tls.reader().read(buf) {
+                Ok(count) => return Ok(count),
+
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+
+                // Terminators often hang up without a close_notify, that's
+                // still just a hang up.
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(0),
+
+                Err(err) => return Err(err),
+            }
+
+            // Nothing decrypted yet, more from the socket. Would block errors
+            // go up from here, and after the end rustls stops asking.
+            self.tls.read_tls(&mut self.socket)?;
+            self.process()?;
+        }
+    }
+}
+
+impl Write for TlsTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    // Taken only when the socket took everything before, otherwise rustls
+    // would keep buffering whatever we give it.
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        self.send_tls()?;
+
+        if self.tls.wants_write() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+
+        let count = self.tls.writer().write_vectored(bufs)?;
+        self.send_tls()?;
+
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_tls()
+    }
+}
+
+impl Transport for TlsTransport {
+    fn wait(&mut self, timeout: Duration) -> io::Result<Ready> {
+        // What a write left behind goes out first, it may be what the other
+        // side is waiting for.
+        self.send_tls()?;
+
+        if self.process()? > 0 {
+            return Ok(Ready {
+                readable: true,
+                writable: false,
+            });
+        }
+
+        self.socket.wait(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tls_tests {
+    use std::{
+        io::Write,
+        net::{SocketAddr, TcpListener},
+        sync::Arc,
+        thread,
+    };
+
+    use rcgen::{generate_simple_self_signed, CertifiedKey};
+    use rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        server::WebPkiClientVerifier,
+        RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+    };
+
+    use super::TlsOptions;
+    use crate::{client::Client, frame::Frame, options::ConnectOptions, test_support::try_receive};
+
+    struct Identity {
+        cert: CertificateDer<'static>,
+        key: PrivateKeyDer<'static>,
+    }
+
+    fn identity(name: &str) -> Identity {
+        let CertifiedKey { cert, key_pair } =
+            generate_simple_self_signed(vec![name.to_string()]).unwrap();
+
+        Identity {
+            cert: cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+        }
+    }
+
+    // A TLS terminator with a BITE behind it that answers everything with
+    // "VALUE". Asks for a client certificate signed by client_ca if given.
+    fn server(server: &Identity, client_ca: Option<&Identity>) -> SocketAddr {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap();
+
+        let builder = match client_ca {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                roots.add(ca.cert.clone()).unwrap();
+
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider)
+                    .build()
+                    .unwrap();
+
+                builder.with_client_cert_verifier(verifier)
+            }
+
+            None => builder.with_no_client_auth(),
+        };
+
+        let config = Arc::new(
+            builder
+                .with_single_cert(vec![server.cert.clone()], server.key.clone_key())
+                .unwrap(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for socket in listener.incoming() {
+                let tls = ServerConnection::new(config.clone()).unwrap();
+                let mut stream = StreamOwned::new(tls, socket.unwrap());
+
+                thread::spawn(move || {
+                    // The handshake fails in some tests, and this with it.
+                    let greeting = Frame::new(7, 0, Vec::new());
+
+                    if stream.write_all(&greeting.encode().unwrap()).is_err() {
+                        return;
+                    }
+
+                    while let Some(frame) = try_receive(&mut stream) {
+                        let reply = Frame::new(7, frame.id, b"VALUE".to_vec());
+                        stream.write_all(&reply.encode().unwrap()).unwrap();
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn round_trip() {
+        let localhost = identity("localhost");
+        let addr = server(&localhost, None);
+
+        let tls = TlsOptions::new()
+            .server_name("localhost")
+            .ca_cert(localhost.cert.clone());
+
+        let mut client = Client::connect_with(ConnectOptions::new().addr(addr).tls(tls)).unwrap();
+        assert_eq!(client.id(), 7);
+
+        // Bigger than a TLS record, so it takes a few.
+        let big = "k".repeat(20_000);
+        assert_eq!(client.get(&big).unwrap(), Some(b"VALUE".to_vec()));
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+    }
+
+    #[test]
+    fn untrusted() {
+        let addr = server(&identity("localhost"), None);
+        let options = ConnectOptions::new()
+            .addr(addr)
+            .tls(TlsOptions::new().server_name("localhost"));
+
+        assert!(Client::connect_with(options).is_err());
+    }
+
+    #[test]
+    fn client_cert() {
+        let localhost = identity("localhost");
+        let client_ca = identity("client");
+        let addr = server(&localhost, Some(&client_ca));
+
+        let tls = TlsOptions::new()
+            .server_name("localhost")
+            .ca_cert(localhost.cert.clone());
+
+        let anonymous = ConnectOptions::new().addr(addr).tls(tls.clone());
+        assert!(Client::connect_with(anonymous).is_err());
+
+        let tls = tls.client_cert(vec![client_ca.cert.clone()], client_ca.key.clone_key());
+        let mut client = Client::connect_with(ConnectOptions::new().addr(addr).tls(tls)).unwrap();
+
+        assert_eq!(client.get("key").unwrap(), Some(b"VALUE".to_vec()));
+    }
+
+    #[test]
+    fn name_from_addr() {
+        let tls = TlsOptions::new();
+
+        let name = tls.name(&ConnectOptions::new().addr("bite.local:1984"));
+        assert_eq!(name.unwrap().to_str(), "bite.local");
+
+        let name = tls.name(&ConnectOptions::new().addr("[::1]:1984"));
+        assert_eq!(name.unwrap().to_str(), "::1");
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, IoSlice, Read, Write},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use mio::{event::Source, net::TcpStream, Events, Interest, Poll, Token};
+
+const SOURCE: Token = Token(0);
+
+// What a wait found, the next read or write tells the rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ready {
+    pub readable: bool,
+    pub writable: bool,
+}
+
+// Anything a Connection can run over. Reads and writes never block, they
+// say WouldBlock instead, and wait is how the connection sleeps until that
+// changes. Readiness may be edge-triggered, wait is only called after a read
+// or a write said WouldBlock.
+pub trait Transport: Read + Write + Send {
+    // Blocks until the transport became readable or writable, or the
+    // timeout passes, then nothing is ready.
+    fn wait(&mut self, timeout: Duration) -> io::Result<Ready>;
+}
+
+// A mio source with its own poll.
+pub struct Polled<S: Source> {
+    source: S,
+    poll: Poll,
+    events: Events,
+}
+
+pub type TcpTransport = Polled<TcpStream>;
+
+#[cfg(unix)]
+pub type UnixTransport = Polled<mio::net::UnixStream>;
+
+impl<S: Source> Polled<S> {
+    pub fn new(mut source: S) -> io::Result<Polled<S>> {
+        let poll = Poll::new()?;
+        poll.registry()
+            .register(&mut source, SOURCE, Interest::READABLE | Interest::WRITABLE)?;
+
+        Ok(Polled {
+            source,
+            poll,
+            events: Events::with_capacity(8),
+        })
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+}
+
+impl<S: Source + Read> Read for Polled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.source.read(buf)
+    }
+}
+
+impl<S: Source + Write> Write for Polled<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.source.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        self.source.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.source.flush()
+    }
+}
+
+impl<S: Source + Read + Write + Send> Transport for Polled<S> {
+    fn wait(&mut self, timeout: Duration) -> io::Result<Ready> {
+        self.poll.poll(&mut self.events, Some(timeout))?;
+
+        let mut ready = Ready::default();
+
+        for event in self.events.iter() {
+            // Errors and hang ups also count, the next read or write is what
+            // tells us what happened.
+            if event.is_readable() || event.is_read_closed() || event.is_error() {
+                ready.readable = true;
+            }
+
+            if event.is_writable() || event.is_write_closed() {
+                ready.writable = true;
+            }
+        }
+
+        Ok(ready)
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+// One direction of a pipe.
+#[derive(Default)]
+struct Channel {
+    buffer: Mutex<Buffer>,
+    changed: Condvar,
+}
+
+impl Channel {
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+// One end of an in-memory duplex pipe, see pipe(). Writes never block, what
+// one end writes the other reads, and dropping an end is like hanging up.
+pub struct Pipe {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+}
+
+pub fn pipe() -> (Pipe, Pipe) {
+    let one = Arc::new(Channel::default());
+    let other = Arc::new(Channel::default());
+
+    (
+        Pipe {
+            incoming: one.clone(),
+            outgoing: other.clone(),
+        },
+        Pipe {
+            incoming: other,
+            outgoing: one,
+        },
+    )
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buffer = self.incoming.buffer.lock().unwrap();
+
+        if buffer.data.is_empty() {
+            return match buffer.closed {
+                true => Ok(0),
+                false => Err(ErrorKind::WouldBlock.into()),
+            };
+        }
+
+        let count = buffer.data.len().min(buf.len());
+
+        for (byte, data) in buf.iter_mut().zip(buffer.data.drain(..count)) {
+            *byte = data;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        let mut buffer = self.outgoing.buffer.lock().unwrap();
+
+        if buffer.closed {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+
+        let mut count = 0;
+
+        for buf in bufs {
+            buffer.data.extend(buf.iter());
+            count += buf.len();
+        }
+
+        drop(buffer);
+        self.outgoing.changed.notify_all();
+
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Pipe {
+    // Always writable, so only reads are waited for.
+    fn wait(&mut self, timeout: Duration) -> io::Result<Ready> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = self.incoming.buffer.lock().unwrap();
+
+        while buffer.data.is_empty() && !buffer.closed {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Ok(Ready::default());
+            }
+
+            buffer = self
+                .incoming
+                .changed
+                .wait_timeout(buffer, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        Ok(Ready {
+            readable: true,
+            writable: false,
+        })
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+#[cfg(test)]
+mod transport_tests {
+    use std::{
+        io::{ErrorKind, Read, Write},
+        thread,
+        time::Duration,
+    };
+
+    use super::{pipe, Ready, Transport};
+
+    #[test]
+    fn pipe_both_ways() {
+        let (mut one, mut other) = pipe();
+
+        one.write_all(b"ping").unwrap();
+        other.write_all(b"pong").unwrap();
+
+        let mut data = [0; 4];
+
+        other.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"ping");
+
+        one.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"pong");
+
+        assert_eq!(
+            one.read(&mut data).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn pipe_wait() {
+        let (mut one, mut other) = pipe();
+
+        assert_eq!(
+            one.wait(Duration::from_millis(10)).unwrap(),
+            Ready::default()
+        );
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            other.write_all(b"late").unwrap();
+        });
+
+        assert!(one.wait(Duration::from_secs(1)).unwrap().readable);
+        writer.join().unwrap();
+
+        // The other end is gone, what it wrote still arrives.
+        let mut data = Vec::new();
+        one.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"late");
+
+        assert_eq!(one.write(b"x").unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,637 @@
+use rand::{thread_rng, Rng};
+
+use bitenc::client::Client;
+use bitenc::command::Command;
+use bitenc::connection::Connection;
+use bitenc::options::DEFAULT_ADDR;
+use bitenc::response::Response;
+use bitenc::util::{get_id, get_read, stamp_header};
+
+use std::env;
+use std::net::TcpStream;
+use std::time::Duration;
+
+// BITE_ADDR points the tests to another server.
+fn addr() -> String {
+    env::var("BITE_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+}
+
+#[test]
+fn empty_message() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    get_id(&mut conn).unwrap();
+
+    conn.try_write(b"".to_vec()).unwrap();
+
+    let response = get_read(&mut conn, 1);
+
+    assert!(
+        response.is_err(),
+        "A message without protocol should be disconnected!"
+    );
+}
+
+#[test]
+fn wrong_client_id() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap().wrapping_add(1);
+
+    conn.try_write(stamp_header(Command::inc("id").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let err = get_read(&mut conn, 1).unwrap_err();
+
+    assert!(
+        err.is_disconnect(),
+        "A message without the correct client id should be disconnected! {:?}",
+        err
+    );
+}
+
+#[test]
+fn set() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::set("set", "SET").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("set").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("set", "").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("set").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 4).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 9, 83, 69, 84, 0, id as u8, 0,
+            0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 6
+        ]
+    );
+}
+
+#[test]
+fn set_if_none() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::delete("maybe").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set_if_none("maybe", "MAYBE").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("maybe").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set_if_none("maybe", "NEW").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("maybe").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 5).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            11, 77, 65, 89, 66, 69, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 11, 77,
+            65, 89, 66, 69
+        ]
+    );
+}
+
+#[test]
+fn inc() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::delete("inc").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::inc("inc").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("inc").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::inc("inc").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("inc").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 5).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 14, 0, 0, 0, 0, 0, 0, 0, 1, 0,
+            id as u8, 0, 0, 0, 14, 0, 0, 0, 0, 0, 0, 0, 1, 0, id as u8, 0, 0, 0, 14, 0, 0, 0, 0, 0,
+            0, 0, 2, 0, id as u8, 0, 0, 0, 14, 0, 0, 0, 0, 0, 0, 0, 2
+        ]
+    );
+}
+
+#[test]
+fn inc_small_key() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::delete("key").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("key", "1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::inc("key").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 3).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            14, 0, 0, 0, 0, 0, 0, 0, 2
+        ]
+    );
+}
+
+#[test]
+fn append() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::delete("append").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::append("append", "APP").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::append("append", "END").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("append").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    // get append needs to be added here ^
+
+    let response = get_read(&mut conn, 4).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            8, 79, 75, 0, id as u8, 0, 0, 0, 12, 65, 80, 80, 69, 78, 68
+        ]
+    );
+}
+
+#[test]
+fn get_delete() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::set("delete", "DELETE").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::delete("delete").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::get("delete").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 3).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            6
+        ]
+    );
+}
+
+#[test]
+fn key_value() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    // This test fails when key_value has more children than expected. We
+    // are assuming an empty database.
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::set("kv.1", "1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("kv.1.2", "1.2").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("kv.1.2.3", "1.2.3").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::keys("kv").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 4).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            8, 79, 75, 0, id as u8, 0, 0, 0, 23, 49, 32, 49, 0, 50, 32, 49, 46, 50, 0, 51, 32, 49,
+            46, 50, 46, 51
+        ]
+    );
+}
+
+#[test]
+fn json_j() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    // This test fails when key_value has more children than expected. We
+    // are assuming an empty database.
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::set("j.1", "1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("j.2", "2").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("j.3.1", "3.1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("j.3.2", "3.2").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::json("j").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 5).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 61, 123, 34, 49, 34,
+            58, 91, 52, 57, 93, 44, 34, 50, 34, 58, 91, 53, 48, 93, 44, 34, 51, 34, 58, 123, 34,
+            49, 34, 58, 91, 53, 49, 44, 52, 54, 44, 52, 57, 93, 44, 34, 50, 34, 58, 91, 53, 49, 44,
+            52, 54, 44, 53, 48, 93, 125, 125
+        ]
+    );
+}
+
+#[test]
+fn json_js() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    // This test fails when key_value has more children than expected. We
+    // are assuming an empty database.
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::set("js.1", "1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("js.2", "2").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("js.3.1", "3.1").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::set("js.3.2", "3.2").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::json_with_key("js").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 5).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 68, 123, 34, 106,
+            115, 34, 58, 123, 34, 49, 34, 58, 91, 52, 57, 93, 44, 34, 50, 34, 58, 91, 53, 48, 93,
+            44, 34, 51, 34, 58, 123, 34, 49, 34, 58, 91, 53, 49, 44, 52, 54, 44, 52, 57, 93, 44,
+            34, 50, 34, 58, 91, 53, 49, 44, 52, 54, 44, 53, 48, 93, 125, 125, 125
+        ]
+    );
+}
+
+#[test]
+fn subscriptions() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    // This test fails when key_value has more children than expected. We
+    // are assuming an empty database.
+
+    let id = get_id(&mut conn).unwrap();
+
+    conn.try_write(stamp_header(Command::subscribe_get("subs").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::subscribe_keys("subs").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::subscribe_json("subs").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    conn.try_write(stamp_header(Command::set("subs", "SET").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::inc("subs").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::append("subs", "+").to_bytes(), id, 0).unwrap())
+        .unwrap();
+    conn.try_write(stamp_header(Command::call("subs", "CALL").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 19).unwrap();
+
+    assert_eq!(
+        response,
+        &[
+            0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0,
+            8, 79, 75, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 9, 83, 69, 84, 0,
+            id as u8, 0, 0, 0, 14, 115, 117, 98, 115, 32, 83, 69, 84, 0, id as u8, 0, 0, 0, 20,
+            123, 34, 115, 117, 98, 115, 34, 58, 34, 83, 69, 84, 34, 125, 0, id as u8, 0, 0, 0, 14,
+            0, 0, 0, 0, 0, 0, 0, 1, 0, id as u8, 0, 0, 0, 14, 0, 0, 0, 0, 0, 0, 0, 1, 0, id as u8,
+            0, 0, 0, 19, 115, 117, 98, 115, 32, 0, 0, 0, 0, 0, 0, 0, 1, 0, id as u8, 0, 0, 0, 65,
+            123, 34, 115, 117, 98, 115, 34, 58, 34, 92, 117, 48, 48, 48, 48, 92, 117, 48, 48, 48,
+            48, 92, 117, 48, 48, 48, 48, 92, 117, 48, 48, 48, 48, 92, 117, 48, 48, 48, 48, 92, 117,
+            48, 48, 48, 48, 92, 117, 48, 48, 48, 48, 92, 117, 48, 48, 48, 49, 34, 125, 0, id as u8,
+            0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 7, 43, 0, id as u8, 0, 0, 0, 12, 115, 117,
+            98, 115, 32, 43, 0, id as u8, 0, 0, 0, 18, 123, 34, 115, 117, 98, 115, 34, 58, 34, 43,
+            34, 125, 0, id as u8, 0, 0, 0, 8, 79, 75, 0, id as u8, 0, 0, 0, 10, 67, 65, 76, 76, 0,
+            id as u8, 0, 0, 0, 15, 115, 117, 98, 115, 32, 67, 65, 76, 76, 0, id as u8, 0, 0, 0, 21,
+            123, 34, 115, 117, 98, 115, 34, 58, 34, 67, 65, 76, 76, 34, 125
+        ]
+    );
+}
+
+#[test]
+fn big_messages_with_wrong_commands() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    for _ in 0..64 {
+        let mut data = [0u8; 65535 - 6];
+        thread_rng().try_fill(&mut data[..]).unwrap();
+
+        conn.try_write(stamp_header(data.to_vec(), id, 0).unwrap())
+            .unwrap();
+    }
+
+    let response = get_read(&mut conn, 64).unwrap();
+
+    let mut expected = Vec::new();
+    for _ in 0..64 {
+        expected.extend_from_slice(&[0, id as u8, 0, 0, 0, 8, 78, 79]);
+    }
+
+    assert_eq!(response, expected);
+}
+
+#[test]
+fn biggest_sets_256() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    let max = 256;
+
+    for i in 0..max {
+        let mut data = [0u8; 65535];
+        thread_rng().try_fill(&mut data[..]).unwrap();
+
+        let mut set = format!("s set.{} ", i).as_bytes().to_vec();
+        set.append(&mut data.to_vec());
+        set.truncate(65535 - 6);
+
+        conn.try_write(stamp_header(set, id, 0).unwrap()).unwrap();
+    }
+
+    let response = get_read(&mut conn, max).unwrap();
+
+    let mut expected = Vec::new();
+    for _ in 0..max {
+        expected.extend_from_slice(&[0, id as u8, 0, 0, 0, 8, 79, 75]);
+    }
+
+    assert_eq!(response, expected);
+}
+
+#[test]
+fn biggest_gets_256() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    let max = 256;
+
+    for i in 0..max {
+        let mut data = [0u8; 65535];
+        thread_rng().try_fill(&mut data[..]).unwrap();
+
+        let mut set = format!("s set.{} ", i).as_bytes().to_vec();
+        set.append(&mut data.to_vec());
+        set.truncate(65535 - 6);
+
+        conn.try_write(stamp_header(set, id, 0).unwrap()).unwrap();
+    }
+
+    let response = get_read(&mut conn, max).unwrap();
+
+    let mut expected = Vec::new();
+    for _ in 0..max {
+        expected.extend_from_slice(&[0, id as u8, 0, 0, 0, 8, 79, 75]);
+    }
+
+    assert_eq!(response, expected);
+}
+
+#[test]
+fn big_get() {
+    let server = TcpStream::connect(addr()).unwrap();
+    server.set_nonblocking(true).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut conn = Connection::new(0, server, addr).unwrap();
+
+    let id = get_id(&mut conn).unwrap();
+
+    const SIZE: usize = 65535;
+
+    let mut data = [0u8; SIZE];
+    thread_rng().try_fill(&mut data[..]).unwrap();
+
+    let mut set = b"s big ".to_vec();
+    set.append(&mut data.to_vec());
+    set.truncate(SIZE - 6);
+
+    conn.try_write(stamp_header(set, id, 0).unwrap()).unwrap();
+    conn.try_write(stamp_header(Command::get("big").to_bytes(), id, 0).unwrap())
+        .unwrap();
+
+    let response = get_read(&mut conn, 2).unwrap();
+
+    println!("response.len {}", response.len());
+    assert_eq!(response.len(), SIZE + 2);
+}
+
+#[test]
+fn client() {
+    let mut client = Client::connect(addr()).unwrap();
+
+    client.set("client", b"CLIENT").unwrap();
+    assert_eq!(client.get("client").unwrap(), Some(b"CLIENT".to_vec()));
+
+    client.delete("client.maybe").unwrap();
+    client.set_if_none("client.maybe", b"MAYBE").unwrap();
+    client.set_if_none("client.maybe", b"NEW").unwrap();
+    assert_eq!(client.get("client.maybe").unwrap(), Some(b"MAYBE".to_vec()));
+
+    client.delete("client.inc").unwrap();
+    assert_eq!(client.inc("client.inc").unwrap(), 1);
+    assert_eq!(client.inc("client.inc").unwrap(), 2);
+
+    client.delete("client.append").unwrap();
+    client.append("client.append", b"APP").unwrap();
+    client.append("client.append", b"END").unwrap();
+    assert_eq!(
+        client.get("client.append").unwrap(),
+        Some(b"APPEND".to_vec())
+    );
+
+    client.delete("client").unwrap();
+    assert_eq!(client.get("client").unwrap(), None);
+
+    client.set("client.kv.1", b"1").unwrap();
+    client.set("client.kv.2", b"2").unwrap();
+    assert_eq!(
+        client.keys("client.kv").unwrap(),
+        vec![
+            ("1".to_string(), b"1".to_vec()),
+            ("2".to_string(), b"2".to_vec())
+        ]
+    );
+    assert_eq!(
+        client.json("client.kv").unwrap().unwrap(),
+        r#"{"1":[49],"2":[50]}"#
+    );
+
+    client.subscribe_get("client.subs").unwrap();
+    client.call("client.subs", b"CALL").unwrap();
+
+    let notification = client
+        .next_notification(Duration::from_secs(1))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(notification.response, Response::Value(b"CALL".to_vec()));
+}
+
+#[test]
+fn pipeline() {
+    let mut client = Client::connect(addr()).unwrap();
+
+    let responses = client
+        .pipeline()
+        .delete("pipeline.inc")
+        .set("pipeline", "PIPE")
+        .inc("pipeline.inc")
+        .inc("pipeline.inc")
+        .get("pipeline")
+        .execute()
+        .unwrap();
+
+    assert_eq!(
+        responses,
+        vec![
+            Response::Ok,
+            Response::Ok,
+            Response::Counter(1),
+            Response::Counter(2),
+            Response::Value(b"PIPE".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn shared_client() {
+    let client = std::sync::Arc::new(bitenc::SharedClient::connect(addr()).unwrap());
+    client.delete("shared.inc").unwrap();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            std::thread::spawn(move || client.inc("shared.inc").unwrap())
+        })
+        .collect();
+
+    let mut counts: Vec<u64> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    counts.sort();
+
+    assert_eq!(counts, vec![1, 2, 3, 4]);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_client() {
+    let client = bitenc::AsyncClient::connect(addr()).await.unwrap();
+
+    client.set("async", b"ASYNC").await.unwrap();
+    client.delete("async.inc").await.unwrap();
+
+    let (value, count) = tokio::join!(client.get("async"), client.inc("async.inc"));
+    assert_eq!(value.unwrap(), Some(b"ASYNC".to_vec()));
+    assert_eq!(count.unwrap(), 1);
+}